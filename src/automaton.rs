@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// A cycle found while running an automaton: the state after `start` steps
+// is the same as the state after `start + length` steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+// Runs a simulation by repeatedly applying the function `step` to a state
+// (usually a grid), keeping count of the steps. The step function should
+// return true if the state changed.
+pub struct Automaton<S, F> {
+    state: S,
+    step: F,
+    steps: usize,
+}
+
+impl<S, F> Automaton<S, F>
+where
+    F: FnMut(&mut S) -> bool,
+{
+    pub fn new( state: S, step: F ) -> Self {
+        Automaton{ state, step, steps: 0 }
+    }
+
+    pub fn state( &self ) -> &S {
+        &self.state
+    }
+
+    pub fn into_state( self ) -> S {
+        self.state
+    }
+
+    // The number of steps run so far.
+    pub fn steps( &self ) -> usize {
+        self.steps
+    }
+
+    // Runs a single step and returns true if the state changed.
+    pub fn step( &mut self ) -> bool {
+        self.steps += 1;
+        (self.step)(&mut self.state)
+    }
+
+    // Runs `n` steps.
+    pub fn run( &mut self, n: usize ) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    // Runs until the state satisfies `done` (which could be the case
+    // already) and returns the number of steps run so far.
+    pub fn run_until<P>( &mut self, mut done: P ) -> usize
+    where
+        P: FnMut(&S) -> bool,
+    {
+        while !done(&self.state) {
+            self.step();
+        }
+        self.steps
+    }
+
+    // Runs until a step does not change the state and returns the number
+    // of steps run so far, including the one with no change.
+    pub fn run_to_fixed_point( &mut self ) -> usize {
+        while self.step() {}
+        self.steps
+    }
+
+    // Runs until the state is the same as a state seen since this was
+    // called, and returns the cycle found. As for the other methods, the
+    // start of the cycle counts all the steps run so far.
+    pub fn run_to_cycle( &mut self ) -> Cycle
+    where
+        S: Clone + Eq + Hash,
+    {
+        let mut seen = HashMap::new();
+        loop {
+            if let Some(start) = seen.insert(self.state.clone(), self.steps) {
+                return Cycle{ start, length: self.steps - start };
+            }
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Edge, SimpleGrid};
+
+    #[test]
+    fn blinker() {
+        // Game of life, with a blinker that oscillates with period 2.
+        let life = |g: &mut SimpleGrid| {
+            let next = g.stencil(3, &Edge::Background(b'.'), |_, w| {
+                let alive = w.iter().filter(|c| ***c == b'#').count() - (*w[4] == b'#') as usize;
+                match (*w[4], alive) {
+                    (b'#', 2) | (_, 3) => b'#',
+                    _ => b'.',
+                }
+            });
+            let changed = next.values().ne(g.values());
+            *g = next;
+            changed
+        };
+
+        let g = SimpleGrid::create_from(".....\n.###.\n.....\n");
+        let mut a = Automaton::new(g, life);
+        a.run(1);
+        assert_eq!(a.state().to_string(), "..#..\n..#..\n..#..\n");
+        assert_eq!(a.run_to_cycle(), Cycle{ start: 1, length: 2 });
+        assert_eq!(a.steps(), 3);
+
+        let g = SimpleGrid::create_from("......\n.##...\n.#....\n......\n");
+        let mut a = Automaton::new(g, life);
+        assert_eq!(a.run_to_fixed_point(), 2);
+        assert_eq!(a.state().to_string(), "......\n.##...\n.##...\n......\n");
+    }
+}
@@ -74,7 +74,7 @@ fn solve(input: &str) -> (usize, usize) {
 
     let mut boards: Vec<_> = boards
         .split("\n\n")
-        .map(|s| BingoBoard::create_from(s))
+        .map(BingoBoard::create_from)
         .collect();
 
     let n_boards = boards.len();
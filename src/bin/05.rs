@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use advent_of_code_2021::grid::{Pos, SimpleGrid};
 
 #[derive(Debug, Clone)]
 struct HydrothermalVent {
-    start: (i16, i16),
-    end: (i16, i16),
+    start: Pos,
+    end: Pos,
 }
 
 impl HydrothermalVent {
@@ -13,34 +13,28 @@ impl HydrothermalVent {
         let (x2, y2) = p2.split_once(',').unwrap();
 
         HydrothermalVent {
-            start: (x1.parse().unwrap(), y1.parse().unwrap()),
-            end: (x2.parse().unwrap(), y2.parse().unwrap()),
+            start: Pos { x: x1.parse().unwrap(), y: y1.parse().unwrap() },
+            end: Pos { x: x2.parse().unwrap(), y: y2.parse().unwrap() },
         }
     }
 
     fn is_horizontal(&self) -> bool {
-        self.start.1 == self.end.1
+        self.start.y == self.end.y
     }
 
     fn is_vertical(&self) -> bool {
-        self.start.0 == self.end.0
+        self.start.x == self.end.x
     }
 }
 
-fn vent_frequency_map(vents: &[HydrothermalVent]) -> HashMap<(i16, i16), usize> {
-    let mut vent_frequency_map = HashMap::new();
+fn vent_frequency_map(vents: &[HydrothermalVent]) -> SimpleGrid<usize> {
+    let x_max = vents.iter().map(|v| v.start.x.max(v.end.x)).max().unwrap();
+    let y_max = vents.iter().map(|v| v.start.y.max(v.end.y)).max().unwrap();
+    let mut vent_frequency_map = SimpleGrid::new(y_max as usize + 1, x_max as usize + 1);
 
     for v in vents {
-        let ((x1, y1), (x2, y2)) = (v.start, v.end);
-        let n_points = i16::max((x2-x1).abs(), (y2-y1).abs());
-        //println!("vent: {:?} -> {:?}", (x1,y1), (x2,y2));
-
-        for p in 0..=n_points {
-            let x = (x1 * (n_points-p) + x2 * p) / n_points;
-            let y = (y1 * (n_points-p) + y2 * p) / n_points;
-            //println!("passing by: {:?}", (x,y));
-            *vent_frequency_map.entry((x,y)).or_insert(0) += 1;
-        }
+        //println!("vent: {:?} -> {:?}", v.start, v.end);
+        vent_frequency_map.draw_line_with(&v.start, &v.end, |n| *n += 1);
     }
 
     vent_frequency_map
@@ -49,7 +43,7 @@ fn vent_frequency_map(vents: &[HydrothermalVent]) -> HashMap<(i16, i16), usize>
 fn solve(input: &str) -> (usize, usize) {
     let vents: Vec<_> = input
         .lines()
-        .map(|s| HydrothermalVent::create_from(s))
+        .map(HydrothermalVent::create_from)
         .collect();
 
     let vents_hv: Vec<_> = vents
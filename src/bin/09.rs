@@ -1,48 +1,29 @@
-use advent_of_code_2021::graph::UnweightedGraph;
-use advent_of_code_2021::grid::{consts::*, Pos, SimpleGrid};
+use advent_of_code_2021::grid::{consts::*, SimpleGrid};
 use std::cmp::Reverse;
 
-struct Cave(SimpleGrid);
-
-impl UnweightedGraph<Pos> for Cave {
-    fn edges(&self, node: &Pos) -> Vec<Pos> {
-        let mut v = Vec::new();
-        let node_value = self.0.get_pos(node).unwrap();
-
-        // Add edges to nearby positions that have a higher value, but less than 9.
-        for d in [UP, DOWN, LEFT, RIGHT] {
-            let next_node = node.go(d);
-            if self.0.get_pos(&next_node).filter(|c| *c < &b'9' && *c > node_value).is_some() {
-                v.push(next_node);
-            }
-        }
-        v
-    }
-}
-
 fn solve(input: &str) -> (u32, usize) {
-    let g = Cave(SimpleGrid::create_from(input));
+    let g = SimpleGrid::create_from(input);
+
+    //println!("{}", g);
 
-    //println!("{}", g.0);
+    // Basins are the regions separated by positions with value 9.
+    let (basin_labels, basins) = g.regions(&NEIGHBORS4, |a, b| *a < b'9' && *b < b'9');
 
     let mut risk_level = 0;
-    let mut basins = vec![];
+    let mut basin_sizes = vec![];
 
-    for (pos, value) in g.0.entries_pos() {
-        if [UP, DOWN, LEFT, RIGHT]
-            .into_iter()
-            .all(|d| value < g.0.get_pos(&pos.go(d)).unwrap_or(&b'9'))
-        {
+    for (pos, value) in g.entries_pos() {
+        if g.neighbors4(&pos).all(|(_, v)| value < v) {
             // The curent position is a low point
             risk_level += (*value - b'0') as u32 + 1;
 
-            basins.push(g.shortest_paths(pos, &[]).len());
+            basin_sizes.push(basins[*basin_labels.get_pos(&pos).unwrap()].size);
         }
     }
 
-    basins.sort_by_key(|b| Reverse(*b));
+    basin_sizes.sort_by_key(|b| Reverse(*b));
 
-    (risk_level, basins[0]*basins[1]*basins[2])
+    (risk_level, basin_sizes[0]*basin_sizes[1]*basin_sizes[2])
 }
 
 fn main() {
@@ -1,47 +1,40 @@
-use advent_of_code_2021::grid::{consts::*, SimpleGrid};
+use advent_of_code_2021::automaton::Automaton;
+use advent_of_code_2021::grid::SimpleGrid;
 
 fn step(g: &mut SimpleGrid) {
     let mut flashes = vec![];
     for (pos, e) in g.entries_mut_pos() {
         *e = match *e {
-            b'9' => { flashes.push(pos); b'0' },
+            9 => { flashes.push(pos); 0 },
             _ => { *e + 1 },
         };
     }
 
     while let Some(pos) = flashes.pop() {
-        for d in [UP, DOWN, LEFT, RIGHT, UP_LEFT, UP_RIGHT, DOWN_LEFT, DOWN_RIGHT] {
-            let new_pos = pos.go(d);
-            if let Some(e) = g.get_mut_pos(&new_pos) {
-                *e = match *e {
-                    b'0' => { b'0' },
-                    b'9' => { flashes.push(new_pos); b'0' },
-                    _ => { *e + 1 },
-                };
-            }
-        }
+        g.update_neighbors8(&pos, |new_pos, e| {
+            *e = match *e {
+                0 => { 0 },
+                9 => { flashes.push(new_pos); 0 },
+                _ => { *e + 1 },
+            };
+        });
     }
 }
 
 fn solve(input: &str) -> (usize, usize) {
-    let mut g = SimpleGrid::create_from(input);
-    //println!("{}", g);
+    let g = SimpleGrid::create_from_with(input, |c| c.to_digit(10).unwrap() as u8);
+    //println!("{}", g.display());
 
-    let mut n_steps = 0;
+    let mut octopuses = Automaton::new(g, |g| { step(g); true });
     let mut n_flashes = 0;
 
-    while n_steps < 100 {
-        step(&mut g);
-        n_steps += 1;
-        n_flashes += g.values().filter(|e| **e == b'0').count();
-        //println!("{}", g);
+    for _ in 0..100 {
+        octopuses.step();
+        n_flashes += octopuses.state().values().filter(|e| **e == 0).count();
+        //println!("{}", octopuses.state().display());
     }
 
-    while !g.values().all(|e| *e == b'0') {
-        step(&mut g);
-        n_steps += 1;
-        //println!("{}", g);
-    }
+    let n_steps = octopuses.run_until(|g| g.values().all(|e| *e == 0));
 
     (n_flashes, n_steps)
 }
@@ -60,21 +53,21 @@ mod tests {
 
     #[test]
     fn example01() {
-        let mut g = SimpleGrid::create_from("\
+        let mut g = SimpleGrid::create_from_with("\
 11111
 19991
 19191
 19991
-11111");
+11111", |c| c.to_digit(10).unwrap() as u8);
         step(&mut g);
-        assert_eq!(g.to_string().trim(), "\
+        assert_eq!(g.display().to_string().trim(), "\
 34543
 40004
 50005
 40004
 34543");
         step(&mut g);
-        assert_eq!(g.to_string().trim(), "\
+        assert_eq!(g.display().to_string().trim(), "\
 45654
 51115
 61116
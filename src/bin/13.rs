@@ -1,21 +1,12 @@
-use advent_of_code_2021::grid::{Pos, SparseGrid};
-use std::collections::HashSet;
+use advent_of_code_2021::grid::{Collision, Pos, SparseGrid};
 
-fn fold(dots: &HashSet<(i16, i16)>, instruction: &str) -> HashSet<(i16, i16)> {
+fn fold(paper: &mut SparseGrid, instruction: &str) {
     let fold_type = &instruction[0..13];
     let fold_position = instruction[13..].parse::<i16>().unwrap();
 
     match fold_type {
-        "fold along x=" => {
-            dots.iter()
-                .map(|(x, y)| (i16::min(*x, 2 * fold_position - *x), *y ))
-                .collect()
-            }
-        "fold along y=" => {
-            dots.iter()
-                .map(|(x, y)| (*x, i16::min(*y, 2 * fold_position - *y)))
-                .collect()
-        },
+        "fold along x=" => paper.fold_x(fold_position, &Collision::Keep),
+        "fold along y=" => paper.fold_y(fold_position, &Collision::Keep),
         _ => panic!(),
     }
 }
@@ -23,30 +14,26 @@ fn fold(dots: &HashSet<(i16, i16)>, instruction: &str) -> HashSet<(i16, i16)> {
 fn solve(input: &str) -> (usize, String) {
     let (dots, instructions) = input.split_once("\n\n").unwrap();
 
-    let mut dots: HashSet<_> = dots
-        .lines()
-        .map(|s| s.split_once(",").unwrap())
-        .map(|(x, y)| (x.parse::<i16>().unwrap(), y.parse::<i16>().unwrap()))
-        .collect();
+    // Arrange the dots on a grid.
+    let mut paper = SparseGrid::new();
+    for (x, y) in dots.lines().map(|s| s.split_once(',').unwrap()) {
+        paper.insert(Pos { x: x.parse().unwrap(), y: y.parse().unwrap() }, '\u{2588}');
+    }
 
     let mut instructions = instructions.lines();
 
     // Apply the first fold instruction.
-    dots = fold(&dots, instructions.next().unwrap());
-    let visible_dots = dots.len();
+    fold(&mut paper, instructions.next().unwrap());
+    let visible_dots = paper.len();
 
     // Apply the remaining fold instructions.
     for i in instructions {
-        dots = fold(&dots, i);
-    }
-
-    // Arrange the dots on a grid.
-    let mut folded_paper = SparseGrid::new();
-    for (x, y) in dots {
-        folded_paper.insert(Pos { x, y }, '\u{2588}');
+        fold(&mut paper, i);
     }
 
-    (visible_dots, folded_paper.to_string())
+    // Read the letters if possible, or return why not with the dots drawn
+    // on the paper.
+    (visible_dots, paper.ocr().unwrap_or_else(|e| format!("{}\n{}", e, paper)))
 }
 
 fn main() {
@@ -85,6 +72,7 @@ mod tests {
 
 fold along y=7
 fold along x=5"), (17, "\
+letters must be 6 or 10 rows high, found 5 rows
 █████
 █   █
 █   █
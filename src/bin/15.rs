@@ -1,43 +1,36 @@
 use advent_of_code_2021::graph::Graph;
-use advent_of_code_2021::grid::{consts::*, Pos, SimpleGrid};
+use advent_of_code_2021::grid::{Pos, SimpleGrid, TiledGrid};
 
-struct Cave(SimpleGrid);
+// The risk in a tile of the full map, computed from the risk in the
+// original tile.
+fn tile_risk(risk: &u8, tile_row: usize, tile_col: usize) -> u8 {
+    (risk + tile_row as u8 + tile_col as u8 - 1) % 9 + 1
+}
 
-impl Graph<Pos> for Cave {
-    fn edges(&self, node: &Pos) -> Vec<(Pos, usize)> {
-        let mut v = Vec::new();
+struct Cave<'a, F>(TiledGrid<'a, u8, F>);
 
+impl<F: Fn(&u8, usize, usize) -> u8> Graph<Pos> for Cave<'_, F> {
+    fn edges(&self, node: &Pos) -> Vec<(Pos, usize)> {
         // Add edges to nearby positions
-        for d in [UP, DOWN, LEFT, RIGHT] {
-            let next_node = node.go(d);
-            if let Some(&value) = self.0.get_pos(&next_node) {
-                v.push((next_node, (value - b'0') as usize));
-            }
-        }
-        v
+        self.0.neighbors4(node)
+            .map(|(next_node, value)| (next_node, value as usize))
+            .collect()
     }
 }
 
 fn solve(input: &str) -> (usize, usize) {
-    let g = Cave(SimpleGrid::create_from(input));
-    let (rows, cols) = (g.0.rows(), g.0.cols());
+    let map = SimpleGrid::create_from_with(input, |c| c.to_digit(10).unwrap() as u8);
 
-    //println!("{}", g.0);
+    //println!("{}", map.display());
 
+    let g = Cave(TiledGrid::new(&map, 1, 1, tile_risk));
     let start = g.0.top_left();
     let end = g.0.bottom_right();
     let risk = g.shortest_paths(start)[&end];
 
-    let (rows5, cols5) = (rows * 5, cols * 5);
-    let mut g5 = Cave(SimpleGrid::new(rows5, cols5));
-    for r in 0..rows5 {
-        for c in 0..cols5 {
-            let g_value = g.0.get(r % rows, c % cols).unwrap();
-            g5.0.set(r, c, (g_value + (r/rows) as u8 + (c/cols) as u8 - b'1') % 9 + b'1');
-        }
-    }
+    let g5 = Cave(TiledGrid::new(&map, 5, 5, tile_risk));
 
-    //println!("{}", g5.0);
+    //println!("{}", g5.0.to_grid().display());
 
     let end5 = g5.0.bottom_right();
     let risk5 = g5.shortest_paths(start)[&end5];
@@ -54,7 +54,7 @@ fn read_packet(bitstream: &mut &[u8]) -> Option<Packet> {
             }
 
             let sub_bitstream = &mut &bitstream[0..length_subpackets];
-            while sub_bitstream.len() > 0 {
+            while !sub_bitstream.is_empty() {
                 subpackets.push(read_packet(sub_bitstream)?);
             }
             *bitstream = &bitstream[length_subpackets..];
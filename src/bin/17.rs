@@ -1,11 +1,9 @@
+use advent_of_code_2021::grid::{Dir, Pos, Rect};
+
 fn solve(input: &str) -> (i64, usize) {
-    let (str_x, str_y) = input.trim().strip_prefix("target area: ").unwrap().split_once(", ").unwrap();
-    let (str_x_min, str_x_max) = str_x.strip_prefix("x=").unwrap().split_once("..").unwrap();
-    let (str_y_min, str_y_max) = str_y.strip_prefix("y=").unwrap().split_once("..").unwrap();
-    let target_x_min = str_x_min.parse::<i64>().unwrap();
-    let target_x_max = str_x_max.parse::<i64>().unwrap();
-    let target_y_min = str_y_min.parse::<i64>().unwrap();
-    let target_y_max = str_y_max.parse::<i64>().unwrap();
+    let target: Rect<i64> = input.trim().strip_prefix("target area: ").unwrap().parse().unwrap();
+    let (target_x_min, target_x_max) = (target.min.x, target.max.x);
+    let (target_y_min, target_y_max) = (target.min.y, target.max.y);
 
     assert!(target_y_max < 0);
     assert!(target_x_min > 0);
@@ -19,18 +17,17 @@ fn solve(input: &str) -> (i64, usize) {
 
     for start_dy in (target_y_min)..=(-target_y_min-1) {
         for start_dx in 1..=target_x_max {
-            let (mut x, mut y) = (0, 0);
-            let (mut dx, mut dy) = (start_dx, start_dy);
-            while y >= target_y_min && x <= target_x_max && (dx > 0 || x >= target_x_min) {
-                if x >= target_x_min && y <= target_y_max {
-                    //println!("Target hit with initial velocity: ({}, {})", start_dx, start_dy);
+            let mut p: Pos<i64> = Pos::default();
+            let mut v = Dir{ dx: start_dx, dy: start_dy };
+            while p.y >= target_y_min && p.x <= target_x_max && (v.dx > 0 || p.x >= target_x_min) {
+                if target.contains(&p) {
+                    //println!("Target hit with initial velocity: {:?}", Dir{ dx: start_dx, dy: start_dy });
                     n_solutions += 1;
                     break;
                 }
-                x += dx;
-                y += dy;
-                if dx > 0 { dx -= 1; }
-                dy -= 1;
+                p += v;
+                // Drag slows the probe horizontally, gravity pulls it down.
+                v += Dir{ dx: -v.dx.signum(), dy: -1 };
             }
         }
     }
@@ -72,7 +72,7 @@ impl Number {
                 if *v >= 10 {
                     *self = Number::Pair(
                         Box::new(Number::Value(*v/2)),
-                        Box::new(Number::Value((*v+1)/2))
+                        Box::new(Number::Value((*v).div_ceil(2)))
                     );
                     true
                 } else {
@@ -107,7 +107,7 @@ impl Number {
 fn solve(input: &str) -> (usize, usize) {
     let numbers: Vec<_> = input
         .lines()
-        .map(|s| Number::create_from(s))
+        .map(Number::create_from)
         .collect();
 
     let (first, rest) = numbers.split_first().unwrap();
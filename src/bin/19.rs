@@ -1,105 +1,69 @@
+use advent_of_code_2021::grid3::{consts::*, Dir3, Pos3, Rotation3};
 use std::collections::HashSet;
 use std::collections::HashMap;
 
-// Represent each rotation as a permutation of the axes 'p' and a
-// reflection of the axes 'r', such that if a rotation (p, r) aligns a
-// scanner to a new frame of reference, after the rotation an obsevation
-//   [obs[0], obs[1], obs[2]]
-// has coordinates
-//   [obs[p[0]]*r[0], obs[p[1]]*r[1], obs[p[2]]*r[2]]
-// in the new frame of reference.
-const ROTATIONS: [([usize; 3], [i16; 3]); 24] = [
-    ([0, 1, 2], [ 1,  1,  1]),
-    ([0, 1, 2], [ 1, -1, -1]),
-    ([0, 1, 2], [-1,  1, -1]),
-    ([0, 1, 2], [-1, -1,  1]),
-    ([1, 2, 0], [ 1,  1,  1]),
-    ([1, 2, 0], [ 1, -1, -1]),
-    ([1, 2, 0], [-1,  1, -1]),
-    ([1, 2, 0], [-1, -1,  1]),
-    ([2, 0, 1], [ 1,  1,  1]),
-    ([2, 0, 1], [ 1, -1, -1]),
-    ([2, 0, 1], [-1,  1, -1]),
-    ([2, 0, 1], [-1, -1,  1]),
-    ([0, 2, 1], [ 1,  1, -1]),
-    ([0, 2, 1], [ 1, -1,  1]),
-    ([0, 2, 1], [-1,  1,  1]),
-    ([0, 2, 1], [-1, -1, -1]),
-    ([2, 1, 0], [ 1,  1, -1]),
-    ([2, 1, 0], [ 1, -1,  1]),
-    ([2, 1, 0], [-1,  1,  1]),
-    ([2, 1, 0], [-1, -1, -1]),
-    ([1, 0, 2], [ 1,  1, -1]),
-    ([1, 0, 2], [ 1, -1,  1]),
-    ([1, 0, 2], [-1,  1,  1]),
-    ([1, 0, 2], [-1, -1, -1]),
-];
-
-// Apply a rotation and a translation to the current frame of reference.
-fn change_reference(rotation: &([usize; 3], [i16; 3]), translation: &[i16; 3], obs: &[i16; 3]) -> [i16; 3] {
-    let (p, r) = rotation;
-    [obs[p[0]]*r[0]-translation[0], obs[p[1]]*r[1]-translation[1], obs[p[2]]*r[2]-translation[2]]
-}
-
 fn solve(input: &str) -> (usize, i16) {
+    // The observations of each scanner, relative to the scanner.
     let scanners: Vec<_> = input
         .split("\n\n")
         .map(|scanner| {
             scanner.lines().skip(1).map(|line| {
                 let mut obs = line.split(',').map(|v| v.parse::<i16>().unwrap());
-                [obs.next().unwrap(), obs.next().unwrap(), obs.next().unwrap()]
+                Dir3{ dx: obs.next().unwrap(), dy: obs.next().unwrap(), dz: obs.next().unwrap() }
             }).collect::<Vec<_>>()
         })
         .collect();
 
     let n_scanners = scanners.len();
 
-    // Rotation and translation to align each scanner.
-    let mut scanners_info = vec![None; n_scanners];
+    // Rotation and position of each scanner, such that an observation obs
+    // of scanner s corresponds to a beacon at position.go(rotation.apply(obs)).
+    let mut scanners_info: Vec<Option<(Rotation3, Pos3)>> = vec![None; n_scanners];
 
     // Positions of the beacons.
     let mut beacons = HashSet::new();
 
     // Assume the frame of scanner[0] is the frame of reference.
-    let (rotation, translation) = (([0, 1, 2], [1, 1, 1]), [0, 0, 0]);
+    let (rotation, position) = (Rotation3::IDENTITY, ORIGIN);
     for obs in &scanners[0] {
-        beacons.insert(change_reference(&rotation, &translation, obs));
+        beacons.insert(position.go(rotation.apply(obs)));
     }
-    scanners_info[0] = Some((rotation, translation));
+    scanners_info[0] = Some((rotation, position));
+
+    let rotations = Rotation3::all();
 
     while !scanners_info.iter().all(|s| s.is_some()) {
         'scanner_loop: for s in 1..n_scanners {
             if scanners_info[s].is_some() {
                 continue;
             }
-            for rotation in &ROTATIONS {
-                // If the rotation is correct and there is a translation t that
-                // aligns the scanner to the frame of reference, then for a beacon
-                // with position p:
+            for rotation in &rotations {
+                // If the rotation is correct and the scanner is at position q,
+                // then for a beacon with position p:
                 //
-                //   change_reference(rotation, t, obs) = p
+                //   q.go(rotation.apply(obs)) = p
                 //
-                // The difference between the rotated observation and p gives
-                // the implied translation t:
+                // Going from p in the opposite direction gives the implied
+                // position of the scanner:
                 //
-                //   change_reference(rotation, p, obs) = t
+                //   p.go(rotation.apply(obs).reverse()) = q
                 //
-                // The same implied translation t should appear every time an
+                // The same implied position q should appear every time an
                 // observation is matched against the corresponding beacon.
-                let mut implied_translations = HashMap::new();
+                let mut implied_positions = HashMap::new();
                 for p in &beacons {
                     for obs in &scanners[s] {
-                        *implied_translations.entry(change_reference(rotation, p, obs)).or_insert(0) += 1;
+                        *implied_positions.entry(p.go(rotation.apply(obs).reverse())).or_insert(0) += 1;
                     }
                 }
-                // Check if the same implied translation was recorded 12 of more times.
-                for (t, count) in implied_translations {
+                // Check if the same implied position was recorded 12 of more times.
+                for (q, count) in implied_positions {
                     if count >= 12 {
-                        //println!("Identified scanner {}: rotation = {:?}, translation = {:?}", s, rotation, t);
+                        //println!("Identified scanner {}: rotation = {:?}, position = {:?}", s, rotation, q);
                         for obs in &scanners[s] {
-                            beacons.insert(change_reference(rotation, &t, obs));
+                            beacons.insert(q.go(rotation.apply(obs)));
                         }
-                        scanners_info[s] = Some((*rotation, t));
+                        scanners_info[s] = Some((*rotation, q));
                         continue 'scanner_loop;
                     }
                 }
@@ -110,11 +74,9 @@ fn solve(input: &str) -> (usize, i16) {
     let mut pairwaise_distances = vec![];
     for i in 0..n_scanners-1 {
         for j in i+1..n_scanners {
-            let t_i = scanners_info[i].unwrap().1;
-            let t_j = scanners_info[j].unwrap().1;
-            pairwaise_distances.push(
-                (t_i[0]-t_j[0]).abs() + (t_i[1]-t_j[1]).abs() + (t_i[2]-t_j[2]).abs()
-            );
+            let q_i = scanners_info[i].unwrap().1;
+            let q_j = scanners_info[j].unwrap().1;
+            pairwaise_distances.push(q_i.distance(&q_j));
         }
     }
 
@@ -1,79 +1,50 @@
-use advent_of_code_2021::grid::SimpleGrid;
+use advent_of_code_2021::automaton::Automaton;
+use advent_of_code_2021::grid::{Edge, InfiniteGrid, Pos, SimpleGrid};
 
-struct InfiniteImage {
-    pixels: SimpleGrid,
-    background: u8,
-}
-
-impl InfiniteImage {
-    fn create_from(s: &str) -> Self {
-        InfiniteImage {
-            pixels: SimpleGrid::create_from(s),
-            background: b'.',
-        }
-    }
+fn enhance(image: &InfiniteGrid, algorithm: &[u8], threads: usize) -> InfiniteGrid {
+    assert!(algorithm.len() == 512);
 
-    fn get(&self, row: isize, col: isize) -> &u8 {
-        if row < 0 || col < 0 {
-            &self.background
-        } else {
-            self.pixels.get(row as usize, col as usize).unwrap_or(&self.background)
-        }
+    let mut background = algorithm[0];
+    if *image.background() == b'#' {
+        background = algorithm[511];
     }
 
-    fn enhance(&self, algorithm: &[u8]) -> InfiniteImage {
-        assert!(algorithm.len() == 512);
-
-        let rows = self.pixels.rows() + 2;
-        let cols = self.pixels.cols() + 2;
-        let mut pixels = SimpleGrid::new(rows, cols);
-
-        for r in 0..rows as isize {
-            for c in 0..cols as isize {
-                let mut index = 0;
-                for (wr, wc) in [(r-2,c-2), (r-2,c-1), (r-2,c),
-                                 (r-1,c-2), (r-1,c-1), (r-1,c),
-                                 (  r,c-2), (  r,c-1), (  r,c)] {
-                    index <<= 1;
-                    if self.get(wr, wc) == &b'#' {
-                        index += 1;
-                    }
-                }
-                pixels.set(r as usize, c as usize, algorithm[index]);
-            }
-        }
-
-        let mut background = algorithm[0];
-        if self.background == b'#' {
-            background = algorithm[511];
-        }
+    // The new image is one pixel larger on each side.
+    let mut pixels = SimpleGrid::new(image.rows() + 2, image.cols() + 2);
+    let edge = Edge::Background(*image.background());
+    image.window().par_stencil_grown_into(3, &edge, 1, &mut pixels, threads, |_, window| {
+        let index = window.iter().fold(0, |index, p| (index << 1) + (**p == b'#') as usize);
+        algorithm[index]
+    });
 
-        InfiniteImage{ pixels, background }
-    }
+    let top_left = image.top_left();
+    InfiniteGrid::from_grid_at(pixels, Pos { x: top_left.x - 1, y: top_left.y - 1 }, background)
+}
 
-    fn lit_pixels(&self) -> usize {
-        assert!(self.background != b'#');
-        self.pixels.values().filter(|c| **c == b'#').count()
-    }
+fn lit_pixels(image: &InfiniteGrid) -> usize {
+    assert!(*image.background() != b'#');
+    image.values().filter(|c| **c == b'#').count()
 }
 
 fn solve(input: &str) -> (usize, usize) {
     let (algorithm, image) = input.split_once("\n\n").unwrap();
 
     let algorithm = algorithm.as_bytes();
-    let mut image = InfiniteImage::create_from(image);
-    //println!("background: {}\n{}", image.background as char, image.pixels);
+    let image = InfiniteGrid::from_grid(SimpleGrid::create_from(image), b'.');
+    //println!("background: {}\n{}", *image.background() as char, image);
 
-    for _ in 0..2 {
-        image = image.enhance(algorithm);
-        //println!("background: {}\n{}", image.background as char, image.pixels);
-    }
-    let lit_pixels_2 = image.lit_pixels();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut images = Automaton::new(image, |image| {
+        *image = enhance(image, algorithm, threads);
+        //println!("background: {}\n{}", *image.background() as char, image);
+        true
+    });
 
-    for _ in 2..50 {
-        image = image.enhance(algorithm);
-    }
-    let lit_pixels_50 = image.lit_pixels();
+    images.run(2);
+    let lit_pixels_2 = lit_pixels(images.state());
+
+    images.run(48);
+    let lit_pixels_50 = lit_pixels(images.state());
 
     (lit_pixels_2, lit_pixels_50)
 }
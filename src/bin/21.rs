@@ -77,7 +77,7 @@ fn play_dirac(start1: u16, start2: u16) -> (u64, u64) {
 }
 
 fn solve(input: &str) -> (u64, u64) {
-    let input1 = input.lines().nth(0).unwrap();
+    let input1 = input.lines().next().unwrap();
     let start1 = input1.strip_prefix("Player 1 starting position: ").unwrap().parse().unwrap();
     let input2 = input.lines().nth(1).unwrap();
     let start2 = input2.strip_prefix("Player 2 starting position: ").unwrap().parse().unwrap();
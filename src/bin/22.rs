@@ -1,46 +1,36 @@
+use advent_of_code_2021::grid3::{Box3, Pos3};
+
 #[derive(Clone, Debug)]
 struct Cuboid {
-    ranges: [(isize, isize); 3],
+    cubes: Box3<i64>,
     on: bool,
 }
 
 impl Cuboid {
     fn create_from(s: &str) -> Self {
         let (on, ranges) = s.split_once(' ').unwrap();
-        let mut ranges = ranges
-            .split(',')
-            .map(|s| s[2..].split_once("..").unwrap())
-            .map(|(l, r)| (l.parse::<isize>().unwrap(), r.parse::<isize>().unwrap()));
 
         Cuboid {
-            ranges: [ranges.next().unwrap(), ranges.next().unwrap(), ranges.next().unwrap()],
+            cubes: ranges.parse().unwrap(),
             on: on == "on",
         }
     }
 
     fn is_initialization(&self) -> bool {
-        (0..3).all(|i| self.ranges[i].0 >= -50 && self.ranges[i].1 <= 50)
+        let region = Box3::new(Pos3 { x: -50, y: -50, z: -50 }, Pos3 { x: 50, y: 50, z: 50 });
+        region.contains(&self.cubes.min) && region.contains(&self.cubes.max)
     }
 
-    fn volume(&self) -> isize {
-        (0..3).map(|i| self.ranges[i].1 - self.ranges[i].0 + 1).product()
+    fn volume(&self) -> i64 {
+        self.cubes.volume()
     }
 
     fn intersect(&self, other: &Self, on: bool) -> Option<Self> {
-        let mut ranges = [(0, 0); 3];
-        for i in 0..3 {
-            ranges[i].0 = isize::max(self.ranges[i].0, other.ranges[i].0);
-            ranges[i].1 = isize::min(self.ranges[i].1, other.ranges[i].1);
-            if !(ranges[i].0 <= ranges[i].1) {
-                return None;
-            }
-        }
-
-        Some(Cuboid { ranges, on })
+        self.cubes.intersect(&other.cubes).map(|cubes| Cuboid { cubes, on })
     }
 }
 
-fn count_on_cubes(cuboids: &Vec<Cuboid>) -> isize {
+fn count_on_cubes(cuboids: &Vec<Cuboid>) -> i64 {
     let mut expanded_cuboids = vec![];
 
     for c in cuboids {
@@ -65,10 +55,10 @@ fn count_on_cubes(cuboids: &Vec<Cuboid>) -> isize {
         .sum()
 }
 
-fn solve(input: &str) -> (isize, isize) {
+fn solve(input: &str) -> (i64, i64) {
     let cuboids: Vec<_> = input
         .lines()
-        .map(|s| Cuboid::create_from(s))
+        .map(Cuboid::create_from)
         .collect();
 
     let init_cuboids: Vec<_> = cuboids
@@ -100,7 +90,7 @@ on x=11..13,y=11..13,z=11..13
 off x=9..11,y=9..11,z=9..11
 on x=10..10,y=10..10,z=10..10"
         .lines()
-        .map(|s| Cuboid::create_from(s))
+        .map(Cuboid::create_from)
         .collect();
         assert_eq!(count_on_cubes(&cuboids), 39);
     }
@@ -58,7 +58,7 @@ fn monad_decode(program: &str) -> Option<[MonadStep; 14]> {
     let mut n_push = 0;
 
     let mut lines = program.lines();
-    for i in 0..14 {
+    for step in result.iter_mut() {
         if lines.next()? != "inp w" { return None; }
         if lines.next()? != "mul x 0" { return None; }
         if lines.next()? != "add x z" { return None; }
@@ -78,7 +78,7 @@ fn monad_decode(program: &str) -> Option<[MonadStep; 14]> {
         if lines.next()? != "mul y x" { return None; }
         if lines.next()? != "add z y" { return None; }
 
-        result[i] = match v1 {
+        *step = match v1 {
             1 if v2 >= 10 => {
                 n_push += 1;
                 MonadStep::Push(v3)
@@ -90,7 +90,7 @@ fn monad_decode(program: &str) -> Option<[MonadStep; 14]> {
         };
     }
 
-    if !lines.next().is_none() { return None };
+    if lines.next().is_some() { return None };
 
     // We expect exactly 7 pushes and 7 pops.
     if n_push != 7 { return None };
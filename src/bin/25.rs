@@ -1,70 +1,65 @@
-use advent_of_code_2021::grid::SimpleGrid;
+use std::fmt::Display;
+use advent_of_code_2021::automaton::Automaton;
+use advent_of_code_2021::grid::{consts::*, BitGrid, Dir, SimpleGrid};
 
-// Advance the state by one step and return true if there was any change.
-fn step(g: &mut SimpleGrid) -> bool {
-    let mut changed = false;
+// The positions of the east-facing and south-facing sea cucumbers.
+#[derive(Clone, Debug)]
+struct Herds {
+    east: BitGrid,
+    south: BitGrid,
+}
 
-    // Advance towards east.
-    for r in 0..g.rows() {
-        let mut c = 0;
-        let mut c_end = g.cols() - 1;
-        if g.get(r, c_end) == Some(&b'>') && g.get(r, c) == Some(&b'.') {
-            // Wrap around the edge of the map.
-            changed = true;
-            g.set(r, c_end, b'.');
-            g.set(r, c, b'>');
-            c += 1;
-            c_end -= 1;
-        }
-        while c < c_end {
-            if g.get(r, c) == Some(&b'>') && g.get(r, c+1) == Some(&b'.') {
-                changed = true;
-                g.set(r, c, b'.');
-                g.set(r, c+1, b'>');
-                c += 2;
-            } else {
-                c += 1;
-            }
+impl Herds {
+    fn create_from(s: &str) -> Self {
+        let g = SimpleGrid::create_from(s);
+        Herds {
+            east: BitGrid::from_grid(&g, |c| *c == b'>'),
+            south: BitGrid::from_grid(&g, |c| *c == b'v'),
         }
     }
+}
 
-    // Advance towards south.
-    for c in 0..g.cols() {
-        let mut r = 0;
-        let mut r_end = g.rows() - 1;
-        if g.get(r_end, c) == Some(&b'v') && g.get(r, c) == Some(&b'.') {
-            // Wrap around the edge of the map.
-            changed = true;
-            g.set(r_end, c, b'.');
-            g.set(r, c, b'v');
-            r += 1;
-            r_end -= 1;
-        }
-        while r < r_end {
-            if g.get(r, c) == Some(&b'v') && g.get(r+1, c) == Some(&b'.') {
-                changed = true;
-                g.set(r, c, b'.');
-                g.set(r+1, c, b'v');
-                r += 2;
-            } else {
-                r += 1;
+impl Display for Herds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for r in 0..self.east.rows() {
+            for c in 0..self.east.cols() {
+                let v = match (self.east.get(r, c), self.south.get(r, c)) {
+                    (Some(true), _) => '>',
+                    (_, Some(true)) => 'v',
+                    _ => '.',
+                };
+                write!(f, "{}", v)?;
             }
+            writeln!(f)?;
         }
+        Ok(())
+    }
+}
+
+// Move all the sea cucumbers of `herd` that can advance in direction `d`
+// (wrapping around the edges), and return true if any of them moved.
+fn advance(herd: &mut BitGrid, other: &BitGrid, d: Dir) -> bool {
+    let free = !&(&*herd | other);
+    let moving = &*herd & &free.shift(d.reverse(), true);
+    if !moving.any() {
+        return false;
     }
 
-    changed
+    *herd ^= &moving;
+    *herd |= &moving.shift(d, true);
+    true
 }
 
-fn solve(input: &str) -> usize {
-    let mut g = SimpleGrid::create_from(input);
+// Advance the state by one step and return true if there was any change.
+fn step(h: &mut Herds) -> bool {
+    let moved_east = advance(&mut h.east, &h.south, RIGHT);
+    let moved_south = advance(&mut h.south, &h.east, DOWN);
 
-    let mut t = 1;
-    while step(&mut g) {
-        t += 1;
-        //println!("{}", g);
-    }
+    moved_east || moved_south
+}
 
-    t
+fn solve(input: &str) -> usize {
+    Automaton::new(Herds::create_from(input), step).run_to_fixed_point()
 }
 
 fn main() {
@@ -81,7 +76,7 @@ mod tests {
 
     #[test]
     fn example01() {
-        let mut g = SimpleGrid::create_from("...>>>>>...\n");
+        let mut g = Herds::create_from("...>>>>>...\n");
         step(&mut g);
         assert_eq!(g.to_string().as_str(), "...>>>>.>..\n");
         step(&mut g);
@@ -90,7 +85,7 @@ mod tests {
 
     #[test]
     fn example02() {
-        let mut g = SimpleGrid::create_from("\
+        let mut g = Herds::create_from("\
 ..........
 .>v....v..
 .......>..
@@ -108,7 +103,7 @@ mod tests {
 
     #[test]
     fn example03() {
-        let mut g = SimpleGrid::create_from("\
+        let mut g = Herds::create_from("\
 ...>...
 .......
 ......>
@@ -15,26 +15,45 @@ where
 
     // Shortest paths computed by breadth-first search.
     fn shortest_paths(&self, start: N, targets: &[N]) -> HashMap<N, usize> {
-        let mut distances = HashMap::new();
-        let mut frontier = VecDeque::new();
+        unweighted_bfs(|n| self.edges(n), start, targets, false).distances
+    }
 
-        frontier.push_back(start.clone());
-        distances.insert(start, 0);
+    // Same as shortest_paths(), but also records the predecessor of each
+    // node, so that the paths can be rebuilt.
+    fn shortest_routes(&self, start: N, targets: &[N]) -> Routes<N> {
+        unweighted_bfs(|n| self.edges(n), start, targets, true)
+    }
+}
 
-        while let Some(node) = frontier.pop_front() {
-            if targets.contains(&node) {
-                break;
-            }
-            let distance = distances[&node];
-            for n in self.edges(&node) {
-                if let Entry::Vacant(e) = distances.entry(n) {
-                    frontier.push_back(e.key().clone());
-                    e.insert(distance + 1);
+// The search behind UnweightedGraph::shortest_paths() and shortest_routes(),
+// which records the predecessors only if `record` is true, as cloning the
+// nodes can be expensive.
+fn unweighted_bfs<N, E>(edges: E, start: N, targets: &[N], record: bool) -> Routes<N>
+where
+    N: Eq + Hash + Clone,
+    E: Fn(&N) -> Vec<N>,
+{
+    let mut routes = Routes::new(start.clone());
+    let mut frontier = VecDeque::new();
+
+    frontier.push_back(start);
+
+    while let Some(node) = frontier.pop_front() {
+        if targets.contains(&node) {
+            break;
+        }
+        let distance = routes.distances[&node];
+        for n in edges(&node) {
+            if let Entry::Vacant(e) = routes.distances.entry(n) {
+                frontier.push_back(e.key().clone());
+                if record {
+                    routes.predecessors.insert(e.key().clone(), node.clone());
                 }
+                e.insert(distance + 1);
             }
         }
-        distances
     }
+    routes
 }
 
 // A trait for structures that represent graphs with nodes of type `N`
@@ -50,68 +69,154 @@ where
     // two paths reach the same node in a different number of steps, the path with
     // fewer steps is always better.
     fn bfs_paths(&self, start: N) -> HashMap<N, usize> {
-        let mut distances = HashMap::new();
-        let mut frontier = VecDeque::new();
-
-        frontier.push_back(start.clone());
-        distances.insert(start, 0);
-
-        while let Some(node) = frontier.pop_front() {
-            let distance = distances[&node];
-            for (n, w) in self.edges(&node) {
-                match distances.entry(n) {
-                    Entry::Vacant(e) => {
-                        frontier.push_back(e.key().clone());
-                        e.insert(distance + w);
-                    }
-                    Entry::Occupied(mut e) if *e.get() > distance + w => {
-                        e.insert(distance + w);
-                    }
-                    _ => {}
-                }
-            }
-        }
-        distances
+        weighted_bfs(|n| self.edges(n), start, false).distances
+    }
+
+    // Same as bfs_paths(), but also records the predecessor of each node.
+    fn bfs_routes(&self, start: N) -> Routes<N> {
+        weighted_bfs(|n| self.edges(n), start, true)
     }
 
     // Implementation of Dijkstra's algorithm for shortest paths,
     // using a binary heap.
     fn shortest_paths(&self, start: N) -> HashMap<N, usize> {
-        let mut distances = HashMap::new();
-        let mut frontier = BinaryHeap::new();
+        dijkstra(|n| self.edges(n), start, false).distances
+    }
 
-        distances.insert(start.clone(), 0);
-        frontier.push(FrontierNode {
-            node: start,
-            distance: 0,
-        });
-
-        while let Some(FrontierNode { node, distance }) = frontier.pop() {
-            if distances.get(&node).filter(|d| **d < distance).is_some() {
-                continue;
-            }
+    // Same as shortest_paths(), but also records the predecessor of each
+    // node.
+    fn shortest_routes(&self, start: N) -> Routes<N> {
+        dijkstra(|n| self.edges(n), start, true)
+    }
+}
 
-            for (n, w) in self.edges(&node) {
-                let next = FrontierNode {
-                    node: n,
-                    distance: distance + w,
-                };
-                match distances.entry(next.node.clone()) {
-                    Entry::Occupied(e) if *e.get() <= next.distance => {
-                        continue;
-                    }
-                    Entry::Occupied(mut e) => {
-                        e.insert(next.distance);
-                        frontier.push(next);
+// The search behind Graph::bfs_paths() and bfs_routes().
+fn weighted_bfs<N, E>(edges: E, start: N, record: bool) -> Routes<N>
+where
+    N: Eq + Hash + Clone,
+    E: Fn(&N) -> Vec<(N, usize)>,
+{
+    let mut routes = Routes::new(start.clone());
+    let mut frontier = VecDeque::new();
+
+    frontier.push_back(start);
+
+    while let Some(node) = frontier.pop_front() {
+        let distance = routes.distances[&node];
+        for (n, w) in edges(&node) {
+            match routes.distances.entry(n) {
+                Entry::Vacant(e) => {
+                    frontier.push_back(e.key().clone());
+                    if record {
+                        routes.predecessors.insert(e.key().clone(), node.clone());
                     }
-                    Entry::Vacant(e) => {
-                        e.insert(next.distance);
-                        frontier.push(next);
+                    e.insert(distance + w);
+                }
+                Entry::Occupied(mut e) if *e.get() > distance + w => {
+                    if record {
+                        routes.predecessors.insert(e.key().clone(), node.clone());
                     }
+                    e.insert(distance + w);
+                }
+                _ => {}
+            }
+        }
+    }
+    routes
+}
+
+// The search behind Graph::shortest_paths() and shortest_routes().
+fn dijkstra<N, E>(edges: E, start: N, record: bool) -> Routes<N>
+where
+    N: Eq + Hash + Clone,
+    E: Fn(&N) -> Vec<(N, usize)>,
+{
+    let mut routes = Routes::new(start.clone());
+    let mut frontier = BinaryHeap::new();
+
+    frontier.push(FrontierNode {
+        node: start,
+        distance: 0,
+    });
+
+    while let Some(FrontierNode { node, distance }) = frontier.pop() {
+        if routes.distances.get(&node).filter(|d| **d < distance).is_some() {
+            continue;
+        }
+
+        for (n, w) in edges(&node) {
+            let next = FrontierNode {
+                node: n,
+                distance: distance + w,
+            };
+            match routes.distances.entry(next.node.clone()) {
+                Entry::Occupied(e) if *e.get() <= next.distance => {
+                    continue;
+                }
+                Entry::Occupied(mut e) => {
+                    e.insert(next.distance);
+                }
+                Entry::Vacant(e) => {
+                    e.insert(next.distance);
                 }
             }
+            if record {
+                routes.predecessors.insert(next.node.clone(), node.clone());
+            }
+            frontier.push(next);
         }
-        distances
+    }
+    routes
+}
+
+// The result of a search from a start node: the distance to each node
+// reached, and the node before it on a shortest path (for all the nodes
+// but the start).
+#[derive(Clone, Debug)]
+pub struct Routes<N> {
+    pub start: N,
+    pub distances: HashMap<N, usize>,
+    pub predecessors: HashMap<N, N>,
+}
+
+impl<N> Routes<N>
+where
+    N: Eq + Hash + Clone,
+{
+    fn new(start: N) -> Self {
+        let mut distances = HashMap::new();
+        distances.insert(start.clone(), 0);
+        Routes {
+            start,
+            distances,
+            predecessors: HashMap::new(),
+        }
+    }
+
+    // The nodes on a shortest path from the start to `target`, both
+    // included, or None if `target` was not reached.
+    pub fn path_to(&self, target: &N) -> Option<Vec<N>> {
+        if !self.distances.contains_key(target) {
+            return None;
+        }
+        let mut path = vec![target.clone()];
+        let mut node = target;
+        while let Some(prev) = self.predecessors.get(node) {
+            path.push(prev.clone());
+            node = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // The edges on a shortest path from the start to `target`, as
+    // (from, to, weight) triples, or None if `target` was not reached.
+    pub fn edges_to(&self, target: &N) -> Option<Vec<(N, N, usize)>> {
+        let path = self.path_to(target)?;
+        Some(path
+            .windows(2)
+            .map(|w| (w[0].clone(), w[1].clone(), self.distances[&w[1]] - self.distances[&w[0]]))
+            .collect())
     }
 }
 
@@ -223,3 +328,43 @@ pub trait Backtracking {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A small graph with a node (5) that cannot be reached from 0.
+    struct Small;
+
+    const EDGES: [(usize, usize, usize); 7] = [(0, 1, 1), (0, 2, 4), (1, 2, 2), (1, 3, 6), (2, 3, 3), (3, 4, 1), (5, 0, 1)];
+
+    impl Graph<usize> for Small {
+        fn edges(&self, node: &usize) -> Vec<(usize, usize)> {
+            EDGES.iter().filter(|e| e.0 == *node).map(|e| (e.1, e.2)).collect()
+        }
+    }
+
+    impl UnweightedGraph<usize> for Small {
+        fn edges(&self, node: &usize) -> Vec<usize> {
+            Graph::edges(self, node).into_iter().map(|(n, _)| n).collect()
+        }
+    }
+
+    #[test]
+    fn routes() {
+        let routes = Graph::shortest_routes(&Small, 0);
+        assert_eq!(routes.distances[&4], 7);
+        assert_eq!(routes.path_to(&4), Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(routes.edges_to(&3), Some(vec![(0, 1, 1), (1, 2, 2), (2, 3, 3)]));
+        assert_eq!(routes.path_to(&0), Some(vec![0]));
+        assert_eq!(routes.path_to(&5), None);
+
+        let bfs = Graph::bfs_routes(&Small, 0);
+        assert_eq!(bfs.distances, routes.distances);
+        assert_eq!(bfs.path_to(&4), routes.path_to(&4));
+
+        let routes = UnweightedGraph::shortest_routes(&Small, 0, &[4]);
+        assert_eq!(routes.path_to(&4), Some(vec![0, 1, 3, 4]));
+        assert_eq!(routes.edges_to(&3), Some(vec![(0, 1, 1), (1, 3, 1)]));
+    }
+}
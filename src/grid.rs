@@ -1,5 +1,12 @@
-use std::collections::HashMap;
-use std::fmt::Display;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use crate::image::{self, Rgb};
 
 pub mod consts {
     use super::{Pos, Dir};
@@ -12,80 +19,480 @@ pub mod consts {
     pub const UP_RIGHT: Dir = Dir{ dx: 1, dy: -1 };
     pub const DOWN_LEFT: Dir = Dir{ dx: -1, dy: 1 };
     pub const DOWN_RIGHT: Dir = Dir{ dx: 1, dy: 1 };
+    pub const NEIGHBORS4: [Dir; 4] = [UP, DOWN, LEFT, RIGHT];
+    pub const NEIGHBORS8: [Dir; 8] = [UP, DOWN, LEFT, RIGHT, UP_LEFT, UP_RIGHT, DOWN_LEFT, DOWN_RIGHT];
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-pub struct Pos {
-    pub x: i16, // steps right from the reference point
-    pub y: i16, // steps down from the reference point
+// The integer types that can be used as coordinates of Pos and Dir.
+pub trait Coord:
+    Copy + Debug + Default + Hash + Ord
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Neg<Output = Self>
+    + AddAssign + SubAssign
+{
+    fn abs( self ) -> Self;
+    fn signum( self ) -> Self;
+    fn as_i64( self ) -> i64;
+    fn from_i64( v: i64 ) -> Self;
 }
 
-impl Pos {
-    pub fn go( &self, dir: Dir ) -> Pos {
+macro_rules! impl_coord {
+    ($($t:ty),*) => {
+        $(
+            impl Coord for $t {
+                fn abs( self ) -> Self { <$t>::abs(self) }
+                fn signum( self ) -> Self { <$t>::signum(self) }
+                fn as_i64( self ) -> i64 { self as i64 }
+                fn from_i64( v: i64 ) -> Self { v as $t }
+            }
+        )*
+    };
+}
+
+impl_coord!(i8, i16, i32, i64, isize);
+
+// A position with coordinates of type `C`. The grids use the default i16
+// coordinates, wider types are for computations on large coordinates.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Pos<C = i16> {
+    pub x: C, // steps right from the reference point
+    pub y: C, // steps down from the reference point
+}
+
+impl<C: Coord> Pos<C> {
+    pub fn go( &self, dir: Dir<C> ) -> Pos<C> {
         Pos{ x: self.x + dir.dx, y: self.y + dir.dy }
     }
 
-    pub fn distance( &self, other: &Pos ) -> i16 {
+    // The Manhattan distance, i.e. the number of steps in the directions
+    // of NEIGHBORS4 needed to go from this position to `other`.
+    pub fn distance( &self, other: &Pos<C> ) -> C {
         (self.x - other.x).abs() + (self.y - other.y).abs()
     }
+
+    // The Chebyshev distance, i.e. the number of steps in the directions
+    // of NEIGHBORS8 needed to go from this position to `other`.
+    pub fn chebyshev_distance( &self, other: &Pos<C> ) -> C {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    // The square of the Euclidean distance.
+    pub fn squared_distance( &self, other: &Pos<C> ) -> C {
+        let d = *other - *self;
+        d.dx * d.dx + d.dy * d.dy
+    }
+
+    // Iterates over the positions on the segment from this position to
+    // `other` (both included), using Bresenham's line algorithm.
+    pub fn line_to( &self, other: &Pos<C> ) -> Line<C> {
+        let dx = (other.x.as_i64() - self.x.as_i64()).abs();
+        let dy = -(other.y.as_i64() - self.y.as_i64()).abs();
+        Line{ p: Some(*self), end: *other, dx, dy, err: dx + dy }
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct Dir {
-    pub dx: i16,
-    pub dy: i16,
+impl<C: Coord> Add<Dir<C>> for Pos<C> {
+    type Output = Pos<C>;
+
+    fn add( self, dir: Dir<C> ) -> Pos<C> {
+        self.go(dir)
+    }
+}
+
+impl<C: Coord> Sub<Dir<C>> for Pos<C> {
+    type Output = Pos<C>;
+
+    fn sub( self, dir: Dir<C> ) -> Pos<C> {
+        self.go(-dir)
+    }
+}
+
+// The direction that leads from `other` to this position.
+impl<C: Coord> Sub for Pos<C> {
+    type Output = Dir<C>;
+
+    fn sub( self, other: Pos<C> ) -> Dir<C> {
+        Dir{ dx: self.x - other.x, dy: self.y - other.y }
+    }
+}
+
+impl<C: Coord> AddAssign<Dir<C>> for Pos<C> {
+    fn add_assign( &mut self, dir: Dir<C> ) {
+        *self = self.go(dir);
+    }
+}
+
+impl<C: Coord> SubAssign<Dir<C>> for Pos<C> {
+    fn sub_assign( &mut self, dir: Dir<C> ) {
+        *self = self.go(-dir);
+    }
+}
+
+// An iterator over the positions on a segment, created by Pos::line_to().
+#[derive(Clone, Debug)]
+pub struct Line<C = i16> {
+    p: Option<Pos<C>>,
+    end: Pos<C>,
+    dx: i64,
+    dy: i64,
+    err: i64,
 }
 
-impl Dir {
-    pub fn add( &self, other: &Dir ) -> Dir {
+impl<C: Coord> Iterator for Line<C> {
+    type Item = Pos<C>;
+
+    fn next( &mut self ) -> Option<Pos<C>> {
+        let p = self.p?;
+        if p == self.end {
+            self.p = None;
+            return Some(p);
+        }
+
+        let mut next = p;
+        let e2 = 2 * self.err;
+        if e2 >= self.dy {
+            self.err += self.dy;
+            next.x += (self.end.x - p.x).signum();
+        }
+        if e2 <= self.dx {
+            self.err += self.dx;
+            next.y += (self.end.y - p.y).signum();
+        }
+        self.p = Some(next);
+        Some(p)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Dir<C = i16> {
+    pub dx: C,
+    pub dy: C,
+}
+
+impl<C: Coord> Dir<C> {
+    pub fn add( &self, other: &Dir<C> ) -> Dir<C> {
         Dir{ dx: self.dx + other.dx, dy: self.dy + other.dy }
     }
 
-    pub fn rotate_left( &self ) -> Dir {
+    pub fn rotate_left( &self ) -> Dir<C> {
         Dir{ dx: self.dy, dy: -self.dx }
     }
 
-    pub fn rotate_right( &self ) -> Dir {
+    pub fn rotate_right( &self ) -> Dir<C> {
         Dir{ dx: -self.dy, dy: self.dx }
     }
 
-    pub fn reverse( &self ) -> Dir {
+    pub fn reverse( &self ) -> Dir<C> {
         Dir{ dx: -self.dx, dy: -self.dy }
     }
 
-    pub fn times( &self, n: i16 ) -> Dir {
+    pub fn times( &self, n: C ) -> Dir<C> {
         Dir{ dx: self.dx * n, dy: self.dy * n }
     }
 }
 
-// A fixed-size grid of bytes, with values accessed by row/column with
-// get()/get_mut()/set(), or by Pos with get_pos()/get_mut_pos()/set_pos().
-#[derive(Clone, Debug)]
-pub struct SimpleGrid {
+impl<C: Coord> Add for Dir<C> {
+    type Output = Dir<C>;
+
+    fn add( self, other: Dir<C> ) -> Dir<C> {
+        Dir::add(&self, &other)
+    }
+}
+
+impl<C: Coord> Sub for Dir<C> {
+    type Output = Dir<C>;
+
+    fn sub( self, other: Dir<C> ) -> Dir<C> {
+        Dir::add(&self, &other.reverse())
+    }
+}
+
+impl<C: Coord> Neg for Dir<C> {
+    type Output = Dir<C>;
+
+    fn neg( self ) -> Dir<C> {
+        self.reverse()
+    }
+}
+
+impl<C: Coord> Mul<C> for Dir<C> {
+    type Output = Dir<C>;
+
+    fn mul( self, n: C ) -> Dir<C> {
+        self.times(n)
+    }
+}
+
+impl<C: Coord> AddAssign for Dir<C> {
+    fn add_assign( &mut self, other: Dir<C> ) {
+        *self = Dir::add(self, &other);
+    }
+}
+
+// An axis-aligned rectangle, given by its top left and bottom right
+// corners (both included).
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Rect<C = i16> {
+    pub min: Pos<C>,
+    pub max: Pos<C>,
+}
+
+impl<C: Coord> Rect<C> {
+    // The rectangle with opposite corners `a` and `b`.
+    pub fn new( a: Pos<C>, b: Pos<C> ) -> Rect<C> {
+        Rect{
+            min: Pos{ x: a.x.min(b.x), y: a.y.min(b.y) },
+            max: Pos{ x: a.x.max(b.x), y: a.y.max(b.y) },
+        }
+    }
+
+    pub fn width( &self ) -> i64 {
+        self.max.x.as_i64() - self.min.x.as_i64() + 1
+    }
+
+    pub fn height( &self ) -> i64 {
+        self.max.y.as_i64() - self.min.y.as_i64() + 1
+    }
+
+    pub fn area( &self ) -> i64 {
+        self.width() * self.height()
+    }
+
+    pub fn contains( &self, p: &Pos<C> ) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+    }
+
+    // The rectangle covered by both rectangles, or None if they do not
+    // overlap.
+    pub fn intersect( &self, other: &Rect<C> ) -> Option<Rect<C>> {
+        let (min_x, max_x) = (self.min.x.max(other.min.x), self.max.x.min(other.max.x));
+        if min_x > max_x {
+            return None;
+        }
+        let (min_y, max_y) = (self.min.y.max(other.min.y), self.max.y.min(other.max.y));
+        if min_y > max_y {
+            return None;
+        }
+        Some(Rect{ min: Pos{ x: min_x, y: min_y }, max: Pos{ x: max_x, y: max_y } })
+    }
+
+    // The smallest rectangle containing both rectangles.
+    pub fn union( &self, other: &Rect<C> ) -> Rect<C> {
+        Rect{
+            min: Pos{ x: self.min.x.min(other.min.x), y: self.min.y.min(other.min.y) },
+            max: Pos{ x: self.max.x.max(other.max.x), y: self.max.y.max(other.max.y) },
+        }
+    }
+
+    // The four corners, clockwise from the top left one (with repetitions
+    // if the rectangle is a single row or column).
+    pub fn corners( &self ) -> impl Iterator<Item = Pos<C>> {
+        let (min, max) = (self.min, self.max);
+        [min, Pos{ x: max.x, y: min.y }, max, Pos{ x: min.x, y: max.y }].into_iter()
+    }
+
+    // All the positions in the rectangle, row by row.
+    pub fn positions( &self ) -> impl Iterator<Item = Pos<C>> {
+        let (min, max) = (self.min, self.max);
+        (min.y.as_i64()..=max.y.as_i64()).flat_map(move |y| {
+            (min.x.as_i64()..=max.x.as_i64()).map(move |x| Pos{ x: C::from_i64(x), y: C::from_i64(y) })
+        })
+    }
+}
+
+// Parses the `x=a..b, y=c..d` notation (a single value can be used for
+// ranges of length 1).
+impl<C: Coord + FromStr> FromStr for Rect<C> {
+    type Err = ParseRangesError;
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        let [(x0, x1), (y0, y1)] = parse_ranges(s, ["x", "y"])?;
+        Ok(Rect::new(Pos{ x: x0, y: y0 }, Pos{ x: x1, y: y1 }))
+    }
+}
+
+// The error returned when parsing ranges of coordinates with the
+// `x=a..b, y=c..d` notation fails, with the part that could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseRangesError(pub String);
+
+impl Display for ParseRangesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid range {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRangesError {}
+
+// Parses the comma separated ranges of coordinates along the given axes,
+// in that order, as pairs of (first, last) values.
+pub(crate) fn parse_ranges<C: FromStr, const N: usize>( s: &str, axes: [&str; N] ) -> Result<[(C, C); N], ParseRangesError> {
+    let mut parts = s.split(',').map(str::trim);
+    let mut ranges = Vec::with_capacity(N);
+    for axis in axes {
+        let part = parts.next().ok_or_else(|| ParseRangesError(s.to_string()))?;
+        let error = || ParseRangesError(part.to_string());
+        let range = part.strip_prefix(axis).and_then(|r| r.strip_prefix('=')).ok_or_else(error)?;
+        let (first, last) = range.split_once("..").unwrap_or((range, range));
+        ranges.push((first.parse().map_err(|_| error())?, last.parse().map_err(|_| error())?));
+    }
+    if let Some(part) = parts.next() {
+        return Err(ParseRangesError(part.to_string()));
+    }
+    Ok(ranges.try_into().unwrap_or_else(|_| unreachable!()))
+}
+
+// The largest number of rows or columns of a parsed grid, as for the
+// sizes asserted by SimpleGrid::filled().
+const MAX_SIZE: usize = i16::MAX as usize - 1;
+
+// The error returned when parsing a grid from text fails, with the row
+// and column (counting from zero) where the problem was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseGridError {
+    pub row: usize,
+    pub col: usize,
+    pub kind: ParseGridErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseGridErrorKind {
+    // The input has no rows or no columns.
+    Empty,
+    // A line has a different length than the first line.
+    RaggedLine,
+    // A character does not represent a valid value.
+    InvalidChar(char),
+    // The row or column is past the largest size supported.
+    TooLarge,
+}
+
+impl Display for ParseGridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.kind {
+            ParseGridErrorKind::Empty => write!(f, "empty grid")?,
+            ParseGridErrorKind::RaggedLine => write!(f, "line has a different length than the first line")?,
+            ParseGridErrorKind::InvalidChar(c) => write!(f, "invalid character {:?}", c)?,
+            ParseGridErrorKind::TooLarge => write!(f, "grid too large")?,
+        }
+        write!(f, " at row {}, column {}", self.row, self.col)
+    }
+}
+
+impl std::error::Error for ParseGridError {}
+
+// A fixed-size grid of values of type `T` (bytes by default), with values
+// accessed by row/column with get()/get_mut()/set(), or by Pos with
+// get_pos()/get_mut_pos()/set_pos().
+// In wrapping mode, positions are taken modulo the size of the grid when
+// accessed by Pos, so that the grid behaves as a torus.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimpleGrid<T = u8> {
     rows: usize,
     cols: usize,
-    data: Vec<u8>,
+    data: Vec<T>,
+    wrapping: bool,
+}
+
+impl<T: Clone + Default> SimpleGrid<T> {
+    pub fn new( rows: usize, cols: usize ) -> SimpleGrid<T> {
+        SimpleGrid::filled(rows, cols, T::default())
+    }
+}
+
+impl SimpleGrid<u8> {
+    pub fn create_from( s: &str ) -> SimpleGrid {
+        SimpleGrid::parse(s).unwrap()
+    }
+
+    // Parses a grid of bytes from text, with one row per line.
+    pub fn parse( s: &str ) -> Result<SimpleGrid, ParseGridError> {
+        let mut cols = None;
+        let mut rows = 0;
+        let mut data = Vec::with_capacity(s.len());
+
+        for line in s.lines() {
+            if rows >= MAX_SIZE || line.len() > MAX_SIZE {
+                let col = if rows >= MAX_SIZE { 0 } else { MAX_SIZE };
+                return Err(ParseGridError{ row: rows, col, kind: ParseGridErrorKind::TooLarge });
+            }
+            let cols = *cols.get_or_insert(line.len());
+            if line.len() != cols {
+                return Err(ParseGridError{ row: rows, col: cols.min(line.len()), kind: ParseGridErrorKind::RaggedLine });
+            }
+            data.extend_from_slice(line.as_bytes());
+            rows += 1;
+        }
+
+        match cols {
+            Some(cols) if cols > 0 => Ok(SimpleGrid{ rows, cols, data, wrapping: false }),
+            _ => Err(ParseGridError{ row: 0, col: 0, kind: ParseGridErrorKind::Empty }),
+        }
+    }
 }
 
-impl SimpleGrid {
-    pub fn new( rows: usize, cols: usize ) -> SimpleGrid {
+impl<T: Clone> SimpleGrid<T> {
+    pub fn filled( rows: usize, cols: usize, v: T ) -> SimpleGrid<T> {
         assert!(rows > 0 && rows < i16::MAX as usize);
         assert!(cols > 0 && cols < i16::MAX as usize);
-        SimpleGrid{ rows, cols, data: vec![0; rows*cols] }
+        SimpleGrid{ rows, cols, data: vec![v; rows*cols], wrapping: false }
     }
 
-    pub fn create_from( s: &str ) -> SimpleGrid {
-        let cols = s.find('\n').unwrap();
+    // Creates a grid computing the value at each row/column with `f`.
+    pub fn from_fn<F>( rows: usize, cols: usize, mut f: F ) -> SimpleGrid<T>
+    where
+        F: FnMut(usize, usize) -> T,
+    {
+        assert!(rows > 0 && rows < i16::MAX as usize);
+        assert!(cols > 0 && cols < i16::MAX as usize);
+        let data = (0..rows*cols).map(|i| f(i / cols, i % cols)).collect();
+        SimpleGrid{ rows, cols, data, wrapping: false }
+    }
+
+    // Creates a grid from text, mapping each character to a value with `f`.
+    pub fn create_from_with<F>( s: &str, mut f: F ) -> SimpleGrid<T>
+    where
+        F: FnMut(char) -> T,
+    {
+        SimpleGrid::parse_with(s, |c| Some(f(c))).unwrap()
+    }
+
+    // Parses a grid from text, with one row per line, mapping each
+    // character to a value with `f`, which returns None for characters
+    // that are not valid.
+    pub fn parse_with<F>( s: &str, mut f: F ) -> Result<SimpleGrid<T>, ParseGridError>
+    where
+        F: FnMut(char) -> Option<T>,
+    {
+        let mut cols = None;
         let mut rows = 0;
         let mut data = Vec::with_capacity(s.len());
 
         for line in s.lines() {
+            if rows >= MAX_SIZE {
+                return Err(ParseGridError{ row: rows, col: 0, kind: ParseGridErrorKind::TooLarge });
+            }
+            let mut n = 0;
+            for c in line.chars() {
+                if n >= MAX_SIZE {
+                    return Err(ParseGridError{ row: rows, col: n, kind: ParseGridErrorKind::TooLarge });
+                }
+                match f(c) {
+                    Some(v) => data.push(v),
+                    None => return Err(ParseGridError{ row: rows, col: n, kind: ParseGridErrorKind::InvalidChar(c) }),
+                }
+                n += 1;
+            }
+            let cols = *cols.get_or_insert(n);
+            if n != cols {
+                return Err(ParseGridError{ row: rows, col: cols.min(n), kind: ParseGridErrorKind::RaggedLine });
+            }
             rows += 1;
-            data.extend_from_slice(line.as_bytes());
-            assert_eq!(data.len(), rows*cols, "input lines have different lengths");
         }
 
-        SimpleGrid{ rows, cols, data }
+        match cols {
+            Some(cols) if cols > 0 => Ok(SimpleGrid{ rows, cols, data, wrapping: false }),
+            _ => Err(ParseGridError{ row: 0, col: 0, kind: ParseGridErrorKind::Empty }),
+        }
     }
 
     pub fn rows( &self ) -> usize {
@@ -112,7 +519,7 @@ impl SimpleGrid {
         Pos { x: self.cols as i16 - 1, y: self.rows as i16 - 1 }
     }
 
-    pub fn get( &self, row: usize, col: usize ) -> Option<&u8> {
+    pub fn get( &self, row: usize, col: usize ) -> Option<&T> {
         if row < self.rows && col < self.cols {
             self.data.get(row * self.cols + col)
         } else {
@@ -120,7 +527,7 @@ impl SimpleGrid {
         }
     }
 
-    pub fn get_mut( &mut self, row: usize, col: usize ) -> Option<&mut u8> {
+    pub fn get_mut( &mut self, row: usize, col: usize ) -> Option<&mut T> {
         if row < self.rows && col < self.cols {
             self.data.get_mut(row * self.cols + col)
         } else {
@@ -128,149 +535,2346 @@ impl SimpleGrid {
         }
     }
 
-    pub fn set( &mut self, row: usize, col: usize, v: u8 ) {
+    pub fn set( &mut self, row: usize, col: usize, v: T ) {
         assert!(row < self.rows && col < self.cols);
         self.data[row * self.cols + col] = v;
     }
 
-    pub fn entries( &self ) -> impl Iterator<Item = ((usize, usize), &u8)> {
+    pub fn entries( &self ) -> impl Iterator<Item = ((usize, usize), &T)> {
         self.data.iter()
             .enumerate()
             .map(|(i, c)| ((i / self.cols, i % self.cols), c))
     }
 
-    pub fn entries_mut( &mut self ) -> impl Iterator<Item = ((usize, usize), &mut u8)> {
+    pub fn entries_mut( &mut self ) -> impl Iterator<Item = ((usize, usize), &mut T)> {
         self.data.iter_mut()
             .enumerate()
             .map(|(i, c)| ((i / self.cols, i % self.cols), c))
     }
 
-    pub fn values( &self ) -> impl Iterator<Item = &u8> {
+    pub fn values( &self ) -> impl Iterator<Item = &T> {
         self.data.iter()
     }
 
-    pub fn get_pos( &self, p: &Pos ) -> Option<&u8> {
-        if p.x >= 0 && (p.x as usize) < self.cols && p.y >= 0 && (p.y as usize) < self.rows {
-            self.data.get(p.y as usize * self.cols + p.x as usize)
-        } else {
-            None
-        }
+    pub fn is_wrapping( &self ) -> bool {
+        self.wrapping
+    }
+
+    pub fn set_wrapping( &mut self, wrapping: bool ) {
+        self.wrapping = wrapping;
     }
 
-    pub fn get_mut_pos( &mut self, p: &Pos ) -> Option<&mut u8> {
+    // Returns the position inside the grid that `p` refers to, which is
+    // `p` itself if it is inside the grid, or `p` taken modulo the size of
+    // the grid in wrapping mode.
+    pub fn wrap_pos( &self, p: &Pos ) -> Option<Pos> {
         if p.x >= 0 && (p.x as usize) < self.cols && p.y >= 0 && (p.y as usize) < self.rows {
-            self.data.get_mut(p.y as usize * self.cols + p.x as usize)
+            Some(*p)
+        } else if self.wrapping {
+            Some(Pos{
+                x: p.x.rem_euclid(self.cols as i16),
+                y: p.y.rem_euclid(self.rows as i16),
+            })
         } else {
             None
         }
     }
 
-    pub fn set_pos( &mut self, p: &Pos, v: u8 ) {
-        assert!(p.x >= 0 && (p.x as usize) < self.cols && p.y >= 0 && (p.y as usize) < self.rows);
-        self.data[p.y as usize * self.cols + p.x as usize] = v;
+    fn index_pos( &self, p: &Pos ) -> Option<usize> {
+        self.wrap_pos(p).map(|p| p.y as usize * self.cols + p.x as usize)
     }
 
-    pub fn entries_pos( &self ) -> impl Iterator<Item = (Pos, &u8)> {
+    pub fn get_pos( &self, p: &Pos ) -> Option<&T> {
+        self.index_pos(p).map(|i| &self.data[i])
+    }
+
+    pub fn get_mut_pos( &mut self, p: &Pos ) -> Option<&mut T> {
+        self.index_pos(p).map(|i| &mut self.data[i])
+    }
+
+    pub fn set_pos( &mut self, p: &Pos, v: T ) {
+        let i = self.index_pos(p).expect("position outside the grid");
+        self.data[i] = v;
+    }
+
+    pub fn entries_pos( &self ) -> impl Iterator<Item = (Pos, &T)> {
         self.data.iter()
             .enumerate()
             .map(|(i, c)| (Pos{ x: (i % self.cols) as i16, y: (i / self.cols) as i16 }, c))
     }
 
-    pub fn entries_mut_pos( &mut self ) -> impl Iterator<Item = (Pos, &mut u8)> {
+    pub fn entries_mut_pos( &mut self ) -> impl Iterator<Item = (Pos, &mut T)> {
         self.data.iter_mut()
             .enumerate()
             .map(|(i, c)| (Pos{ x: (i % self.cols) as i16, y: (i / self.cols) as i16 }, c))
     }
-}
 
-impl Display for SimpleGrid {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for row in 0..self.rows {
-            for col in 0..self.cols {
-                let c = *self.get(row, col).filter(|v| v.is_ascii_graphic()).unwrap_or(&b' ') as char;
-                write!(f, "{}", c)?;
+    // Iterates over the positions reached from `p` going in each of the
+    // directions in `dirs` that are inside the grid, with their values.
+    pub fn neighbors_with<'a>( &'a self, p: &Pos, dirs: &'a [Dir] ) -> impl Iterator<Item = (Pos, &'a T)> + 'a {
+        let p = *p;
+        dirs.iter()
+            .filter_map(move |d| self.wrap_pos(&p.go(*d)))
+            .map(|n| (n, self.get_pos(&n).unwrap()))
+    }
+
+    pub fn neighbors4( &self, p: &Pos ) -> impl Iterator<Item = (Pos, &T)> {
+        self.neighbors_with(p, &consts::NEIGHBORS4)
+    }
+
+    pub fn neighbors8( &self, p: &Pos ) -> impl Iterator<Item = (Pos, &T)> {
+        self.neighbors_with(p, &consts::NEIGHBORS8)
+    }
+
+    // Calls `f` on each of the positions reached from `p` going in each of
+    // the directions in `dirs` that are inside the grid, with a mutable
+    // reference to their value.
+    pub fn update_neighbors_with<F>( &mut self, p: &Pos, dirs: &[Dir], mut f: F )
+    where
+        F: FnMut(Pos, &mut T),
+    {
+        for d in dirs {
+            if let Some(n) = self.wrap_pos(&p.go(*d)) {
+                f(n, self.get_mut_pos(&n).unwrap());
             }
-            writeln!(f)?;
         }
-        Ok(())
     }
-}
 
-// A sparse grid of Unicode characters, with values accessed by
-// Pos with get()/insert()/remove().
-#[derive(Clone, Debug)]
-pub struct SparseGrid {
-    pub symbols: HashMap<Pos, char>,
-}
+    pub fn update_neighbors4<F: FnMut(Pos, &mut T)>( &mut self, p: &Pos, f: F ) {
+        self.update_neighbors_with(p, &consts::NEIGHBORS4, f)
+    }
 
-impl SparseGrid {
-    pub fn new() -> Self {
-        SparseGrid { symbols: HashMap::new() }
+    pub fn update_neighbors8<F: FnMut(Pos, &mut T)>( &mut self, p: &Pos, f: F ) {
+        self.update_neighbors_with(p, &consts::NEIGHBORS8, f)
     }
-    pub fn create_from( s: &str ) -> Self {
-        let mut symbols = HashMap::new();
-        let mut x = 0;
-        let mut y = 0;
-        for c in s.chars() {
-            match c {
-                '\n' => { y += 1; x = 0; },
-                c => {
-                    if !c.is_ascii_whitespace() {
-                        symbols.insert(Pos { x, y }, c);
-                    }
-                    x += 1;
-                }
+
+    // Iterates over the positions (with their values) reached going from
+    // `p` (excluded) in the direction `dir`, until leaving the grid. On a
+    // wrapping grid the ray wraps around, and stops before getting back
+    // to `p`.
+    pub fn ray( &self, p: &Pos, dir: Dir ) -> impl Iterator<Item = (Pos, &T)> {
+        let start = self.wrap_pos(p);
+        let mut current = start;
+        std::iter::from_fn(move || {
+            let next = self.wrap_pos(&current?.go(dir))?;
+            if Some(next) == start {
+                return None;
+            }
+            current = Some(next);
+            Some((next, self.get_pos(&next).unwrap()))
+        })
+    }
+
+    // Like ray(), but also stops before the first value for which `stop`
+    // returns true.
+    pub fn ray_until<F>( &self, p: &Pos, dir: Dir, mut stop: F ) -> impl Iterator<Item = (Pos, &T)>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.ray(p, dir).take_while(move |(_, v)| !stop(v))
+    }
+
+    // The first position (with its value) along the ray from `p` in the
+    // direction `dir` for which `hit` returns true.
+    pub fn first_hit<F>( &self, p: &Pos, dir: Dir, mut hit: F ) -> Option<(Pos, &T)>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.ray(p, dir).find(|(_, v)| hit(v))
+    }
+
+    // The first hits (see first_hit()) from `p` in each of the eight
+    // directions of NEIGHBORS8, i.e. the values for which `hit` returns
+    // true that can be seen from `p` when they block the view.
+    pub fn visible_from<'a, F>( &'a self, p: &Pos, hit: F ) -> impl Iterator<Item = (Pos, &'a T)> + 'a
+    where
+        F: Fn(&T) -> bool + 'a,
+    {
+        let p = *p;
+        consts::NEIGHBORS8.iter()
+            .filter_map(move |d| self.first_hit(&p, *d, &hit))
+    }
+
+    // Sets the value `v` at the positions on the segment from `from` to
+    // `to` that are inside the grid (even in wrapping mode).
+    pub fn draw_line( &mut self, from: &Pos, to: &Pos, v: T ) {
+        self.draw_line_with(from, to, |e| *e = v.clone());
+    }
+
+    // Calls `f` on the values at the positions on the segment from `from`
+    // to `to` that are inside the grid (even in wrapping mode).
+    pub fn draw_line_with<F>( &mut self, from: &Pos, to: &Pos, mut f: F )
+    where
+        F: FnMut(&mut T),
+    {
+        for p in from.line_to(to) {
+            if p.x < 0 || p.y < 0 {
+                continue;
+            }
+            if let Some(e) = self.get_mut(p.y as usize, p.x as usize) {
+                f(e);
             }
         }
-        SparseGrid { symbols }
     }
 
-    pub fn x_min( &self ) -> i16 {
-        *self.symbols.keys().map(|Pos { x, .. }| x).min().unwrap_or(&0)
+    // Returns a copy of the grid rotated by 90 degrees counterclockwise.
+    pub fn rotate_left( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.cols, self.rows, |r, c| self.data[c * self.cols + self.cols - 1 - r].clone())
     }
 
-    pub fn x_max( &self ) -> i16 {
-        *self.symbols.keys().map(|Pos { x, .. }| x).max().unwrap_or(&0)
+    // Returns a copy of the grid rotated by 90 degrees clockwise.
+    pub fn rotate_right( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.cols, self.rows, |r, c| self.data[(self.rows - 1 - c) * self.cols + r].clone())
     }
 
-    pub fn y_min( &self ) -> i16 {
-        *self.symbols.keys().map(|Pos { y, .. }| y).min().unwrap_or(&0)
+    // Returns a copy of the grid with rows and columns swapped.
+    pub fn transpose( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.cols, self.rows, |r, c| self.data[c * self.cols + r].clone())
     }
 
-    pub fn y_max( &self ) -> i16 {
-        *self.symbols.keys().map(|Pos { y, .. }| y).max().unwrap_or(&0)
+    // Returns a copy of the grid mirrored left to right.
+    pub fn flip_horizontal( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.rows, self.cols, |r, c| self.data[r * self.cols + self.cols - 1 - c].clone())
     }
 
-    pub fn get(&self, p: &Pos) -> Option<&char> {
-        self.symbols.get(p)
+    // Returns a copy of the grid mirrored top to bottom.
+    pub fn flip_vertical( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.rows, self.cols, |r, c| self.data[(self.rows - 1 - r) * self.cols + c].clone())
     }
 
-    pub fn insert( &mut self, p: Pos, c: char ) -> Option<char> {
-        self.symbols.insert(p, c)
+    // Returns a read-only view of the `rows` x `cols` rectangle with the
+    // top left corner at `top_left`.
+    pub fn subgrid( &self, top_left: &Pos, rows: usize, cols: usize ) -> SubGrid<'_, T> {
+        assert!(top_left.x >= 0 && top_left.x as usize + cols <= self.cols);
+        assert!(top_left.y >= 0 && top_left.y as usize + rows <= self.rows);
+        SubGrid{ grid: self, row0: top_left.y as usize, col0: top_left.x as usize, rows, cols }
     }
 
-    pub fn remove( &mut self, p: &Pos ) -> Option<char> {
-        self.symbols.remove(p)
+    // Returns a copy of the `rows` x `cols` rectangle with the top left
+    // corner at `top_left`, or None if the rectangle is empty (as a grid
+    // cannot be).
+    pub fn crop( &self, top_left: &Pos, rows: usize, cols: usize ) -> Option<SimpleGrid<T>> {
+        let view = self.subgrid(top_left, rows, cols);
+        (rows > 0 && cols > 0).then(|| view.to_grid())
     }
 
-    pub fn find( &self, c: char ) -> Option<&Pos> {
-        self.symbols.iter().find(|(_, sym)| **sym == c ).map(|(pos, _)| pos)
+    // The rectangle covered by the grid.
+    pub fn rect( &self ) -> Rect {
+        Rect{ min: Pos{ x: 0, y: 0 }, max: Pos{ x: self.cols as i16 - 1, y: self.rows as i16 - 1 } }
     }
-}
 
-impl Display for SparseGrid {
-    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
-        let x0 = self.x_min();
-        let x1 = self.x_max();
-        let y0 = self.y_min();
-        let y1 = self.y_max();
+    // Returns a copy of the part of the grid in the rectangle `r`, which
+    // must be inside the grid.
+    pub fn crop_rect( &self, r: &Rect ) -> SimpleGrid<T> {
+        self.subgrid(&r.min, r.height() as usize, r.width() as usize).to_grid()
+    }
 
-        for y in y0..=y1 {
-            for x in x0..=x1 {
-                write!(f, "{}", self.get(&Pos { x, y }).unwrap_or(&' '))?;
+    // Iterates over the positions (with their values) of the grid that
+    // are inside the rectangle `r`, row by row.
+    pub fn entries_rect( &self, r: &Rect ) -> impl Iterator<Item = (Pos, &T)> {
+        self.rect().intersect(r)
+            .into_iter()
+            .flat_map(|r| r.positions())
+            .map(|p| (p, self.get_pos(&p).unwrap()))
+    }
+
+    // Returns the positions reachable from `start` moving in the directions
+    // in `dirs`, where a move from a value `a` to a value `b` is possible if
+    // `connected(a, b)` is true.
+    pub fn flood_fill<F>( &self, start: &Pos, dirs: &[Dir], connected: F ) -> Vec<Pos>
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        let mut visited = SimpleGrid::filled(self.rows, self.cols, false);
+        let mut reached = vec![];
+        let mut stack = vec![];
+
+        if let Some(start) = self.wrap_pos(start) {
+            visited.set_pos(&start, true);
+            stack.push(start);
+        }
+        while let Some(p) = stack.pop() {
+            reached.push(p);
+            let v = self.get_pos(&p).unwrap();
+            for (n, w) in self.neighbors_with(&p, dirs) {
+                if !visited.get_pos(&n).unwrap() && connected(v, w) {
+                    visited.set_pos(&n, true);
+                    stack.push(n);
+                }
             }
-            writeln!(f)?;
         }
-        Ok(())
+        reached
+    }
+
+    // Splits the grid into connected regions, where two nearby positions
+    // (in one of the directions in `dirs`) with values `a` and `b` are
+    // connected if `connected(a, b)` is true. Returns a grid with the index
+    // of the region of each position, and the list of regions.
+    pub fn regions<F>( &self, dirs: &[Dir], connected: F ) -> (SimpleGrid<usize>, Vec<Region>)
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        let mut labels = SimpleGrid::filled(self.rows, self.cols, usize::MAX);
+        labels.wrapping = self.wrapping;
+        let mut regions = vec![];
+        let mut stack = vec![];
+
+        for i in 0..self.data.len() {
+            if labels.data[i] != usize::MAX {
+                continue;
+            }
+            let start = Pos{ x: (i % self.cols) as i16, y: (i / self.cols) as i16 };
+            let mut region = Region{ size: 0, top_left: start, bottom_right: start, representative: start };
+            labels.data[i] = regions.len();
+            stack.push(start);
+
+            while let Some(p) = stack.pop() {
+                region.size += 1;
+                region.top_left = Pos{ x: region.top_left.x.min(p.x), y: region.top_left.y.min(p.y) };
+                region.bottom_right = Pos{ x: region.bottom_right.x.max(p.x), y: region.bottom_right.y.max(p.y) };
+
+                let v = self.get_pos(&p).unwrap();
+                for (n, w) in self.neighbors_with(&p, dirs) {
+                    if *labels.get_pos(&n).unwrap() == usize::MAX && connected(v, w) {
+                        labels.set_pos(&n, regions.len());
+                        stack.push(n);
+                    }
+                }
+            }
+            regions.push(region);
+        }
+        (labels, regions)
+    }
+
+    // Computes in a single breadth-first search the number of moves (in
+    // the directions in `dirs`) from each position to the nearest of the
+    // `sources`, moving only through the positions whose values satisfy
+    // `passable` (the sources themselves are always included). Returns a
+    // grid with the distances, and a grid with the index in `sources` of
+    // the nearest source (the smallest one for ties), both None for the
+    // positions that cannot be reached.
+    pub fn distance_field<F>( &self, sources: &[Pos], dirs: &[Dir], passable: F ) -> (SimpleGrid<Option<usize>>, SimpleGrid<Option<usize>>)
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut distances = SimpleGrid::filled(self.rows, self.cols, None);
+        let mut labels = SimpleGrid::filled(self.rows, self.cols, None);
+        distances.wrapping = self.wrapping;
+        labels.wrapping = self.wrapping;
+
+        let mut frontier = vec![];
+        for (i, s) in sources.iter().enumerate() {
+            if labels.get_pos(s).is_some_and(|l| l.is_none()) {
+                distances.set_pos(s, Some(0));
+                labels.set_pos(s, Some(i));
+                frontier.push(*s);
+            }
+        }
+
+        // Handle one distance at a time, so that the labels of a level are
+        // final before they are propagated to the next one.
+        let mut d = 0;
+        while !frontier.is_empty() {
+            d += 1;
+            let mut next = vec![];
+            for p in &frontier {
+                let label = *labels.get_pos(p).unwrap();
+                for (n, v) in self.neighbors_with(p, dirs) {
+                    if !passable(v) {
+                        continue;
+                    }
+                    match *distances.get_pos(&n).unwrap() {
+                        None => {
+                            distances.set_pos(&n, Some(d));
+                            labels.set_pos(&n, label);
+                            next.push(n);
+                        },
+                        Some(dn) if dn == d && label < *labels.get_pos(&n).unwrap() => {
+                            labels.set_pos(&n, label);
+                        },
+                        _ => {},
+                    }
+                }
+            }
+            frontier = next;
+        }
+        (distances, labels)
+    }
+
+    // Returns the value at the given row/column, which can be outside the
+    // grid, handling the edges according to `edge`.
+    fn edge_get<'a>( &'a self, row: isize, col: isize, edge: &'a Edge<T> ) -> &'a T {
+        let (rows, cols) = (self.rows as isize, self.cols as isize);
+        let (row, col) = match edge {
+            _ if row >= 0 && row < rows && col >= 0 && col < cols => (row, col),
+            Edge::Background(v) => return v,
+            Edge::Clamp => (row.clamp(0, rows - 1), col.clamp(0, cols - 1)),
+            Edge::Wrap => (row.rem_euclid(rows), col.rem_euclid(cols)),
+        };
+        &self.data[row as usize * self.cols + col as usize]
+    }
+
+    // Applies `f` to the `k` x `k` neighbourhood of each position (with `k`
+    // odd and the position at the centre), writing the results in `dest`,
+    // which must have the same size as the grid. The neighbourhood is passed
+    // to `f` as a slice of values in reading order, and positions outside
+    // the grid are handled according to `edge`.
+    pub fn stencil_into<U, F>( &self, k: usize, edge: &Edge<T>, dest: &mut SimpleGrid<U>, mut f: F )
+    where
+        F: FnMut(Pos, &[&T]) -> U,
+    {
+        assert!(k % 2 == 1, "the neighbourhood size must be odd");
+        assert!(dest.rows == self.rows && dest.cols == self.cols, "the destination grid has a different size");
+        self.stencil_band(k, edge, 0, 0, &mut dest.data, &mut f);
+    }
+
+    // Same as stencil_into(), but splits the rows into bands handled by
+    // `threads` threads in parallel. The results do not depend on the
+    // number of threads.
+    pub fn par_stencil_into<U, F>( &self, k: usize, edge: &Edge<T>, dest: &mut SimpleGrid<U>, threads: usize, f: F )
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(Pos, &[&T]) -> U + Sync,
+    {
+        assert!(k % 2 == 1, "the neighbourhood size must be odd");
+        self.par_stencil_grown_into(k, edge, 0, dest, threads, f);
+    }
+
+    // Same as par_stencil_into(), but with `dest` larger than the grid by
+    // `border` rows and columns on each side, which are computed with the
+    // positions outside the grid handled according to `edge`. The
+    // positions passed to `f` are those of the grid, so they are negative
+    // in the top and left borders.
+    pub fn par_stencil_grown_into<U, F>( &self, k: usize, edge: &Edge<T>, border: usize, dest: &mut SimpleGrid<U>, threads: usize, f: F )
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(Pos, &[&T]) -> U + Sync,
+    {
+        assert!(k % 2 == 1, "the neighbourhood size must be odd");
+        assert!(dest.rows == self.rows + 2 * border && dest.cols == self.cols + 2 * border, "the destination grid has a different size");
+        Self::par_bands(dest.rows, dest.cols, &mut dest.data, threads, |first, band| {
+            self.stencil_band(k, edge, border, first, band, &mut &f)
+        });
+    }
+
+    // Computes the values of `dest` from index `first`, for stencil_into(),
+    // with `dest` larger than the grid by `border` on each side.
+    fn stencil_band<U, F>( &self, k: usize, edge: &Edge<T>, border: usize, first: usize, dest: &mut [U], f: &mut F )
+    where
+        F: FnMut(Pos, &[&T]) -> U,
+    {
+        let r = (k / 2) as isize;
+        let (rows, cols) = (self.rows as isize, self.cols as isize);
+        let dest_cols = self.cols + 2 * border;
+        // The window is overwritten in place for each position.
+        let mut window = vec![&self.data[0]; k * k];
+        // The bands are made of whole rows.
+        for (dr, dest_row) in dest.chunks_mut(dest_cols).enumerate() {
+            let row = (first / dest_cols + dr) as isize - border as isize;
+            let inner_row = row >= r && row + r < rows;
+            for (dc, v) in dest_row.iter_mut().enumerate() {
+                let col = dc as isize - border as isize;
+                if inner_row && col >= r && col + r < cols {
+                    // The whole neighbourhood is inside the grid.
+                    // The usual sizes are passed as constants, so that the
+                    // copy is unrolled.
+                    let start = (row-r) as usize * self.cols + (col-r) as usize;
+                    match k {
+                        3 => self.copy_window(3, start, &mut window),
+                        5 => self.copy_window(5, start, &mut window),
+                        _ => self.copy_window(k, start, &mut window),
+                    }
+                } else {
+                    let positions = (row-r..=row+r).flat_map(|wr| (col-r..=col+r).map(move |wc| (wr, wc)));
+                    for (w, (wr, wc)) in window.iter_mut().zip(positions) {
+                        *w = self.edge_get(wr, wc, edge);
+                    }
+                }
+                *v = f(Pos{ x: col as i16, y: row as i16 }, &window);
+            }
+        }
+    }
+
+    // Sets `window` to the `k` x `k` values from index `start`, for
+    // stencil_band().
+    #[inline(always)]
+    fn copy_window<'a>( &'a self, k: usize, start: usize, window: &mut [&'a T] ) {
+        for wr in 0..k {
+            let data_row = &self.data[start + wr * self.cols..][..k];
+            for (w, v) in window[wr * k..][..k].iter_mut().zip(data_row) {
+                *w = v;
+            }
+        }
+    }
+
+    // Sets each value of `dest`, which must have the same size as the grid,
+    // to `f` applied to the position and the value there in this grid.
+    // The rows are split into bands handled by `threads` threads in
+    // parallel, and the results do not depend on the number of threads.
+    pub fn par_map_into<U, F>( &self, dest: &mut SimpleGrid<U>, threads: usize, f: F )
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(Pos, &T) -> U + Sync,
+    {
+        assert!(dest.rows == self.rows && dest.cols == self.cols, "the destination grid has a different size");
+        Self::par_bands(self.rows, self.cols, &mut dest.data, threads, |first, band| {
+            for (i, v) in band.iter_mut().enumerate() {
+                let i = first + i;
+                *v = f(Pos{ x: (i % self.cols) as i16, y: (i / self.cols) as i16 }, &self.data[i]);
+            }
+        });
+    }
+
+    // Splits `data` (the values of a grid of `rows` x `cols`) into bands of
+    // whole rows and calls `f` on each of them, with the index of their
+    // first value, using up to `threads` scoped threads. A single band is
+    // handled on the current thread.
+    fn par_bands<U, F>( rows: usize, cols: usize, data: &mut [U], threads: usize, f: F )
+    where
+        U: Send,
+        F: Fn(usize, &mut [U]) + Sync,
+    {
+        assert!(threads > 0, "at least one thread is needed");
+        let band_size = rows.div_ceil(threads) * cols;
+        if band_size >= data.len() {
+            f(0, data);
+            return;
+        }
+        std::thread::scope(|s| {
+            for (b, band) in data.chunks_mut(band_size).enumerate() {
+                let f = &f;
+                s.spawn(move || f(b * band_size, band));
+            }
+        });
+    }
+
+    // Same as stencil_into(), but returns the results in a new grid.
+    pub fn stencil<U, F>( &self, k: usize, edge: &Edge<T>, f: F ) -> SimpleGrid<U>
+    where
+        U: Clone + Default,
+        F: FnMut(Pos, &[&T]) -> U,
+    {
+        let mut dest = SimpleGrid::new(self.rows, self.cols);
+        self.stencil_into(k, edge, &mut dest, f);
+        dest
+    }
+
+    // Reads the letters (in one of the Advent of Code fonts) drawn with
+    // the positions where `marked` is true.
+    pub fn ocr_with<F>( &self, marked: F ) -> Result<String, OcrError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        ocr(self.top_left(), self.bottom_right(), |p| marked(self.get_pos(p).unwrap()))
+    }
+
+    // Writes the grid as a PBM image, with black pixels where `black` is true.
+    pub fn write_pbm<W: Write, F: Fn(&T) -> bool>( &self, w: &mut W, black: F ) -> io::Result<()> {
+        image::write_pbm(w, self.rows, self.cols, |r, c| black(self.get(r, c).unwrap()))
+    }
+
+    // Writes the grid as a PGM image, with gray levels given by `gray`.
+    pub fn write_pgm<W: Write, F: Fn(&T) -> u8>( &self, w: &mut W, gray: F ) -> io::Result<()> {
+        image::write_pgm(w, self.rows, self.cols, |r, c| gray(self.get(r, c).unwrap()))
+    }
+
+    // Writes the grid as a PPM image, with colours given by `color`.
+    pub fn write_ppm<W: Write, F: Fn(&T) -> Rgb>( &self, w: &mut W, color: F ) -> io::Result<()> {
+        image::write_ppm(w, self.rows, self.cols, |r, c| color(self.get(r, c).unwrap()))
+    }
+
+    // Writes the grid as an SVG image, with squares of side `cell_size`
+    // coloured by `color` (transparent where it returns None).
+    pub fn write_svg<W, F>( &self, w: &mut W, cell_size: usize, color: F ) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(&T) -> Option<Rgb>,
+    {
+        image::write_svg(w, self.rows, self.cols, cell_size, |r, c| color(self.get(r, c).unwrap()))
+    }
+
+    // Displays the grid by rendering each value with `f`.
+    pub fn display_with<'a, D, F>( &'a self, f: F ) -> impl Display + 'a
+    where
+        D: Display,
+        F: Fn(&'a T) -> D + 'a,
+    {
+        GridDisplay{ grid: self, render: f }
+    }
+
+    // Displays the grid for a terminal, drawing each value with the
+    // character and the colour (if any) given by `render`, and highlighting
+    // the positions in `highlight` in reverse video. If `color` is false,
+    // only the characters are drawn, without any escape codes.
+    pub fn display_ansi<'a, F>( &'a self, render: F, highlight: &'a HashSet<Pos>, color: bool ) -> impl Display + 'a
+    where
+        F: Fn(&T) -> (char, Option<Rgb>) + 'a,
+    {
+        AnsiDisplay{ grid: self, render, highlight, color }
+    }
+
+    // Displays the grid using the `Display` implementation of the values.
+    pub fn display( &self ) -> impl Display + '_
+    where
+        T: Display,
+    {
+        self.display_with(|v| v)
+    }
+}
+
+impl Display for SimpleGrid<u8> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let c = *self.get(row, col).filter(|v| v.is_ascii_graphic()).unwrap_or(&b' ') as char;
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// How positions outside a SimpleGrid are handled by stencils: they can
+// have a fixed background value, take the value of the nearest position
+// inside the grid, or wrap around the grid.
+#[derive(Clone, Debug)]
+pub enum Edge<T> {
+    Background(T),
+    Clamp,
+    Wrap,
+}
+
+// A connected region of a SimpleGrid, as found by SimpleGrid::regions().
+// The representative is the first position of the region in reading order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub size: usize,
+    pub top_left: Pos,
+    pub bottom_right: Pos,
+    pub representative: Pos,
+}
+
+// A read-only view of a rectangle inside a SimpleGrid, with values
+// accessed by row/column with get() or by Pos with get_pos(), relative to
+// the top left corner of the rectangle.
+#[derive(Debug)]
+pub struct SubGrid<'a, T = u8> {
+    grid: &'a SimpleGrid<T>,
+    row0: usize,
+    col0: usize,
+    rows: usize,
+    cols: usize,
+}
+
+// Implemented by hand, as derive would require `T: Copy`.
+impl<T> Clone for SubGrid<'_, T> {
+    fn clone( &self ) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SubGrid<'_, T> {}
+
+impl<'a, T: Clone> SubGrid<'a, T> {
+    pub fn rows( &self ) -> usize {
+        self.rows
+    }
+
+    pub fn cols( &self ) -> usize {
+        self.cols
+    }
+
+    pub fn get( &self, row: usize, col: usize ) -> Option<&'a T> {
+        if row < self.rows && col < self.cols {
+            self.grid.get(self.row0 + row, self.col0 + col)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_pos( &self, p: &Pos ) -> Option<&'a T> {
+        if p.x >= 0 && p.y >= 0 {
+            self.get(p.y as usize, p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn entries_pos( &self ) -> impl Iterator<Item = (Pos, &'a T)> + '_ {
+        (0..self.rows * self.cols)
+            .map(|i| Pos{ x: (i % self.cols) as i16, y: (i / self.cols) as i16 })
+            .map(|p| (p, self.get_pos(&p).unwrap()))
+    }
+
+    pub fn values( &self ) -> impl Iterator<Item = &'a T> + '_ {
+        self.entries_pos().map(|(_, v)| v)
+    }
+
+    pub fn to_grid( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.rows, self.cols, |r, c| self.get(r, c).unwrap().clone())
+    }
+
+    // Returns true if the view has the same size and values as `pattern`.
+    pub fn matches( &self, pattern: &SimpleGrid<T> ) -> bool
+    where
+        T: PartialEq,
+    {
+        self.rows == pattern.rows
+            && self.cols == pattern.cols
+            && self.values().eq(pattern.values())
+    }
+}
+
+// A read-only virtual grid made of `tile_rows` x `tile_cols` copies of a
+// SimpleGrid, where the value in each copy is computed on access by the
+// function `transform`, which gets the value in the base grid and the
+// row/column of the tile.
+pub struct TiledGrid<'a, T, F> {
+    grid: &'a SimpleGrid<T>,
+    tile_rows: usize,
+    tile_cols: usize,
+    transform: F,
+}
+
+impl<'a, T, F> TiledGrid<'a, T, F>
+where
+    T: Clone,
+    F: Fn(&T, usize, usize) -> T,
+{
+    pub fn new( grid: &'a SimpleGrid<T>, tile_rows: usize, tile_cols: usize, transform: F ) -> Self {
+        assert!(tile_rows > 0 && grid.rows() * tile_rows < i16::MAX as usize);
+        assert!(tile_cols > 0 && grid.cols() * tile_cols < i16::MAX as usize);
+        TiledGrid{ grid, tile_rows, tile_cols, transform }
+    }
+
+    pub fn rows( &self ) -> usize {
+        self.grid.rows() * self.tile_rows
+    }
+
+    pub fn cols( &self ) -> usize {
+        self.grid.cols() * self.tile_cols
+    }
+
+    pub fn top_left( &self ) -> Pos {
+        Pos { x: 0, y: 0 }
+    }
+
+    pub fn bottom_right( &self ) -> Pos {
+        Pos { x: self.cols() as i16 - 1, y: self.rows() as i16 - 1 }
+    }
+
+    pub fn get( &self, row: usize, col: usize ) -> Option<T> {
+        if row < self.rows() && col < self.cols() {
+            let (rows, cols) = (self.grid.rows(), self.grid.cols());
+            let v = self.grid.get(row % rows, col % cols).unwrap();
+            Some((self.transform)(v, row / rows, col / cols))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_pos( &self, p: &Pos ) -> Option<T> {
+        if p.x >= 0 && p.y >= 0 {
+            self.get(p.y as usize, p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn entries_pos( &self ) -> impl Iterator<Item = (Pos, T)> + '_ {
+        (0..self.rows() * self.cols())
+            .map(|i| Pos{ x: (i % self.cols()) as i16, y: (i / self.cols()) as i16 })
+            .map(|p| (p, self.get_pos(&p).unwrap()))
+    }
+
+    pub fn values( &self ) -> impl Iterator<Item = T> + '_ {
+        self.entries_pos().map(|(_, v)| v)
+    }
+
+    pub fn neighbors_with<'b>( &'b self, p: &Pos, dirs: &'b [Dir] ) -> impl Iterator<Item = (Pos, T)> + 'b {
+        let p = *p;
+        dirs.iter()
+            .map(move |d| p.go(*d))
+            .filter_map(|n| self.get_pos(&n).map(|v| (n, v)))
+    }
+
+    pub fn neighbors4( &self, p: &Pos ) -> impl Iterator<Item = (Pos, T)> + '_ {
+        self.neighbors_with(p, &consts::NEIGHBORS4)
+    }
+
+    pub fn neighbors8( &self, p: &Pos ) -> impl Iterator<Item = (Pos, T)> + '_ {
+        self.neighbors_with(p, &consts::NEIGHBORS8)
+    }
+
+    // Returns a copy of the whole virtual grid.
+    pub fn to_grid( &self ) -> SimpleGrid<T> {
+        SimpleGrid::from_fn(self.rows(), self.cols(), |r, c| self.get(r, c).unwrap())
+    }
+}
+
+struct AnsiDisplay<'a, T, F> {
+    grid: &'a SimpleGrid<T>,
+    render: F,
+    highlight: &'a HashSet<Pos>,
+    color: bool,
+}
+
+impl<T: Clone, F: Fn(&T) -> (char, Option<Rgb>)> Display for AnsiDisplay<'_, T, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (p, v) in self.grid.entries_pos() {
+            let (c, fg) = (self.render)(v);
+            if self.color {
+                image::write_ansi(f, c, fg, self.highlight.contains(&p))?;
+            } else {
+                write!(f, "{}", c)?;
+            }
+            if p.x as usize == self.grid.cols - 1 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct GridDisplay<'a, T, F> {
+    grid: &'a SimpleGrid<T>,
+    render: F,
+}
+
+impl<'a, T: Clone, D: Display, F: Fn(&'a T) -> D> Display for GridDisplay<'a, T, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                write!(f, "{}", (self.render)(self.grid.get(row, col).unwrap()))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// A grid extending infinitely in all directions, made of a finite window
+// (a SimpleGrid whose top left corner is at `origin`) and a background value
+// for all positions outside the window. Values can be read at any Pos with
+// get(); writing with set() outside the window makes the window grow.
+#[derive(Clone, Debug)]
+pub struct InfiniteGrid<T = u8> {
+    grid: SimpleGrid<T>,
+    origin: Pos,
+    background: T,
+}
+
+impl<T: Clone> InfiniteGrid<T> {
+    pub fn new( top_left: Pos, rows: usize, cols: usize, background: T ) -> InfiniteGrid<T> {
+        let grid = SimpleGrid::filled(rows, cols, background.clone());
+        InfiniteGrid{ grid, origin: top_left, background }
+    }
+
+    // The wrapping mode of `grid` is turned off, as the positions outside
+    // the window hold the background.
+    pub fn from_grid( grid: SimpleGrid<T>, background: T ) -> InfiniteGrid<T> {
+        InfiniteGrid::from_grid_at(grid, Pos{ x: 0, y: 0 }, background)
+    }
+
+    // Same as from_grid(), with the top left corner of the window at
+    // `top_left`.
+    pub fn from_grid_at( mut grid: SimpleGrid<T>, top_left: Pos, background: T ) -> InfiniteGrid<T> {
+        grid.set_wrapping(false);
+        InfiniteGrid{ grid, origin: top_left, background }
+    }
+
+    pub fn window( &self ) -> &SimpleGrid<T> {
+        &self.grid
+    }
+
+    pub fn background( &self ) -> &T {
+        &self.background
+    }
+
+    pub fn set_background( &mut self, background: T ) {
+        self.background = background;
+    }
+
+    pub fn rows( &self ) -> usize {
+        self.grid.rows()
+    }
+
+    pub fn cols( &self ) -> usize {
+        self.grid.cols()
+    }
+
+    pub fn top_left( &self ) -> Pos {
+        self.origin
+    }
+
+    pub fn bottom_right( &self ) -> Pos {
+        Pos{ x: self.origin.x + self.cols() as i16 - 1, y: self.origin.y + self.rows() as i16 - 1 }
+    }
+
+    fn window_pos( &self, p: &Pos ) -> Pos {
+        Pos{ x: p.x - self.origin.x, y: p.y - self.origin.y }
+    }
+
+    pub fn get( &self, p: &Pos ) -> &T {
+        self.grid.get_pos(&self.window_pos(p)).unwrap_or(&self.background)
+    }
+
+    pub fn set( &mut self, p: &Pos, v: T ) {
+        let top_left = self.top_left();
+        let bottom_right = self.bottom_right();
+        if p.x < top_left.x || p.y < top_left.y || p.x > bottom_right.x || p.y > bottom_right.y {
+            self.resize(
+                Pos{ x: p.x.min(top_left.x), y: p.y.min(top_left.y) },
+                Pos{ x: p.x.max(bottom_right.x), y: p.y.max(bottom_right.y) },
+            );
+        }
+        let p = self.window_pos(p);
+        self.grid.set_pos(&p, v);
+    }
+
+    // Extends the window by `n` positions in each direction.
+    pub fn grow( &mut self, n: usize ) {
+        let n = n as i16;
+        let top_left = self.top_left();
+        let bottom_right = self.bottom_right();
+        self.resize(
+            Pos{ x: top_left.x - n, y: top_left.y - n },
+            Pos{ x: bottom_right.x + n, y: bottom_right.y + n },
+        );
+    }
+
+    // Changes the window to the rectangle between `top_left` and
+    // `bottom_right`, dropping the values that fall outside of it.
+    fn resize( &mut self, top_left: Pos, bottom_right: Pos ) {
+        let rows = (bottom_right.y - top_left.y + 1) as usize;
+        let cols = (bottom_right.x - top_left.x + 1) as usize;
+        let mut grid = SimpleGrid::filled(rows, cols, self.background.clone());
+        // Copies the overlap of the old and new windows row by row.
+        let old_bottom_right = self.bottom_right();
+        let (x0, x1) = (top_left.x.max(self.origin.x), bottom_right.x.min(old_bottom_right.x));
+        let (y0, y1) = (top_left.y.max(self.origin.y), bottom_right.y.min(old_bottom_right.y));
+        if x0 <= x1 {
+            let n = (x1 - x0 + 1) as usize;
+            for y in y0..=y1 {
+                let src = (y - self.origin.y) as usize * self.cols() + (x0 - self.origin.x) as usize;
+                let dst = (y - top_left.y) as usize * cols + (x0 - top_left.x) as usize;
+                grid.data[dst..dst+n].clone_from_slice(&self.grid.data[src..src+n]);
+            }
+        }
+        self.grid = grid;
+        self.origin = top_left;
+    }
+
+    pub fn entries_pos( &self ) -> impl Iterator<Item = (Pos, &T)> {
+        let origin = self.origin;
+        self.grid.entries_pos()
+            .map(move |(p, v)| (Pos{ x: p.x + origin.x, y: p.y + origin.y }, v))
+    }
+
+    pub fn entries_mut_pos( &mut self ) -> impl Iterator<Item = (Pos, &mut T)> {
+        let origin = self.origin;
+        self.grid.entries_mut_pos()
+            .map(move |(p, v)| (Pos{ x: p.x + origin.x, y: p.y + origin.y }, v))
+    }
+
+    pub fn values( &self ) -> impl Iterator<Item = &T> {
+        self.grid.values()
+    }
+
+    // Iterates over the values of the window. The window itself is not
+    // exposed mutably, as changing its wrapping mode would break get().
+    pub fn values_mut( &mut self ) -> impl Iterator<Item = &mut T> {
+        self.grid.entries_mut_pos().map(|(_, v)| v)
+    }
+}
+
+impl<T: Clone + PartialEq> InfiniteGrid<T> {
+    // Shrinks the window by removing the border rows and columns that only
+    // contain the background value (the window always keeps at least one
+    // position).
+    pub fn trim( &mut self ) {
+        let mut non_background = self.entries_pos()
+            .filter(|(_, v)| **v != self.background)
+            .map(|(p, _)| p);
+
+        let (mut top_left, mut bottom_right) = match non_background.next() {
+            Some(p) => (p, p),
+            None => (self.origin, self.origin),
+        };
+        for p in non_background {
+            top_left = Pos{ x: top_left.x.min(p.x), y: top_left.y.min(p.y) };
+            bottom_right = Pos{ x: bottom_right.x.max(p.x), y: bottom_right.y.max(p.y) };
+        }
+
+        if top_left != self.top_left() || bottom_right != self.bottom_right() {
+            self.resize(top_left, bottom_right);
+        }
+    }
+}
+
+impl Display for InfiniteGrid<u8> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.grid)
+    }
+}
+
+// A fixed-size grid of booleans packed in 64-bit words, row by row, with
+// bit `c % 64` of word `c / 64` of a row holding column `c`. The bits past
+// the last column are always zero. Whole grids can be shifted and combined
+// with the &, |, ^ and ! operators a word at a time.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BitGrid {
+    rows: usize,
+    cols: usize,
+    words: usize, // per row
+    data: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new( rows: usize, cols: usize ) -> BitGrid {
+        assert!(rows > 0 && rows < i16::MAX as usize);
+        assert!(cols > 0 && cols < i16::MAX as usize);
+        let words = cols.div_ceil(64);
+        BitGrid{ rows, cols, words, data: vec![0; rows * words] }
+    }
+
+    pub fn from_fn<F>( rows: usize, cols: usize, mut f: F ) -> BitGrid
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let mut g = BitGrid::new(rows, cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                g.set(r, c, f(r, c));
+            }
+        }
+        g
+    }
+
+    // Creates a grid of the same size as `grid`, with the bits set where
+    // `f` returns true for the values of `grid`.
+    pub fn from_grid<T: Clone, F>( grid: &SimpleGrid<T>, f: F ) -> BitGrid
+    where
+        F: Fn(&T) -> bool,
+    {
+        BitGrid::from_fn(grid.rows(), grid.cols(), |r, c| f(grid.get(r, c).unwrap()))
+    }
+
+    pub fn rows( &self ) -> usize {
+        self.rows
+    }
+
+    pub fn cols( &self ) -> usize {
+        self.cols
+    }
+
+    pub fn get( &self, row: usize, col: usize ) -> Option<bool> {
+        if row < self.rows && col < self.cols {
+            Some(self.data[row * self.words + col / 64] >> (col % 64) & 1 == 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn set( &mut self, row: usize, col: usize, v: bool ) {
+        assert!(row < self.rows && col < self.cols, "position outside the grid");
+        let word = &mut self.data[row * self.words + col / 64];
+        if v {
+            *word |= 1 << (col % 64);
+        } else {
+            *word &= !(1 << (col % 64));
+        }
+    }
+
+    pub fn get_pos( &self, p: &Pos ) -> Option<bool> {
+        if p.x >= 0 && p.y >= 0 {
+            self.get(p.y as usize, p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_pos( &mut self, p: &Pos, v: bool ) {
+        assert!(p.x >= 0 && p.y >= 0, "position outside the grid");
+        self.set(p.y as usize, p.x as usize, v);
+    }
+
+    // The number of bits set.
+    pub fn count_ones( &self ) -> usize {
+        self.data.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // Tells if any bit is set.
+    pub fn any( &self ) -> bool {
+        self.data.iter().any(|w| *w != 0)
+    }
+
+    // Iterates over the positions of the bits set, row by row.
+    pub fn positions( &self ) -> impl Iterator<Item = Pos> + '_ {
+        self.data.iter().enumerate().flat_map(move |(i, w)| {
+            let (row, col0) = (i / self.words, i % self.words * 64);
+            let mut w = *w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
+                }
+                let bit = w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(Pos{ x: (col0 + bit) as i16, y: row as i16 })
+            })
+        })
+    }
+
+    // Returns a copy of the grid with every bit moved in direction `d`.
+    // The bits moved outside the grid wrap around if `wrap` is true, and
+    // are lost otherwise.
+    pub fn shift( &self, d: Dir, wrap: bool ) -> BitGrid {
+        let mut shifted = BitGrid::new(self.rows, self.cols);
+        let (rows, cols) = (self.rows as i64, self.cols as i64);
+        let (dx, dy) = (d.dx as i64, d.dy as i64);
+        for r in 0..rows {
+            let to = if wrap { (r + dy).rem_euclid(rows) } else { r + dy };
+            if !(0..rows).contains(&to) {
+                continue;
+            }
+            let src = &self.data[r as usize * self.words..][..self.words];
+            let dst = &mut shifted.data[to as usize * self.words..][..self.words];
+            if wrap {
+                let k = dx.rem_euclid(cols) as usize;
+                shift_up(src, k, dst);
+                if k > 0 {
+                    shift_down(src, self.cols - k, dst);
+                }
+            } else if dx >= 0 {
+                shift_up(src, dx.min(cols) as usize, dst);
+            } else {
+                shift_down(src, (-dx).min(cols) as usize, dst);
+            }
+        }
+        shifted.clear_padding();
+        shifted
+    }
+
+    fn clear_padding( &mut self ) {
+        if !self.cols.is_multiple_of(64) {
+            let mask = (1 << (self.cols % 64)) - 1;
+            for row in self.data.chunks_mut(self.words) {
+                row[self.words - 1] &= mask;
+            }
+        }
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>( &mut self, other: &BitGrid, f: F ) {
+        assert!(self.rows == other.rows && self.cols == other.cols, "grids of different sizes");
+        for (a, b) in self.data.iter_mut().zip(&other.data) {
+            *a = f(*a, *b);
+        }
+    }
+}
+
+// ORs into `dst` the bits of `src` moved `k` positions up (towards the
+// higher columns), dropping those moved past the end of `dst`.
+fn shift_up( src: &[u64], k: usize, dst: &mut [u64] ) {
+    let (words, bits) = (k / 64, k % 64);
+    for i in words..dst.len() {
+        dst[i] |= src[i - words] << bits;
+        if bits > 0 && i > words {
+            dst[i] |= src[i - words - 1] >> (64 - bits);
+        }
+    }
+}
+
+// ORs into `dst` the bits of `src` moved `k` positions down (towards the
+// lower columns), dropping those moved before the start of `dst`.
+fn shift_down( src: &[u64], k: usize, dst: &mut [u64] ) {
+    let (words, bits) = (k / 64, k % 64);
+    for i in 0..dst.len().saturating_sub(words) {
+        dst[i] |= src[i + words] >> bits;
+        if bits > 0 && i + words + 1 < src.len() {
+            dst[i] |= src[i + words + 1] << (64 - bits);
+        }
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign( &mut self, other: &BitGrid ) {
+        self.combine(other, |a, b| a & b);
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign( &mut self, other: &BitGrid ) {
+        self.combine(other, |a, b| a | b);
+    }
+}
+
+impl BitXorAssign<&BitGrid> for BitGrid {
+    fn bitxor_assign( &mut self, other: &BitGrid ) {
+        self.combine(other, |a, b| a ^ b);
+    }
+}
+
+impl BitAnd for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitand( self, other: &BitGrid ) -> BitGrid {
+        let mut g = self.clone();
+        g &= other;
+        g
+    }
+}
+
+impl BitOr for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitor( self, other: &BitGrid ) -> BitGrid {
+        let mut g = self.clone();
+        g |= other;
+        g
+    }
+}
+
+impl BitXor for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitxor( self, other: &BitGrid ) -> BitGrid {
+        let mut g = self.clone();
+        g ^= other;
+        g
+    }
+}
+
+impl Not for &BitGrid {
+    type Output = BitGrid;
+
+    fn not( self ) -> BitGrid {
+        let mut g = self.clone();
+        for w in &mut g.data {
+            *w = !*w;
+        }
+        g.clear_padding();
+        g
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                write!(f, "{}", if self.get(r, c).unwrap() { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// The letters of the Advent of Code fonts, 4 positions wide and 6 high,
+// and 6 positions wide and 10 high.
+const FONT_6: [(char, [&str; 6]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const FONT_10: [(char, [&str; 10]); 15] = [
+    ('A', ["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', ["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', [".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', [".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', ["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', ["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', ["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', ["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', ["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', ["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', ["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', ["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', ["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+// The error returned when the letters in a grid cannot be recognised.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OcrError {
+    // The marked positions do not span 6 or 10 rows (the value is the
+    // number of rows spanned).
+    UnsupportedHeight(usize),
+    // Some glyphs were not recognised: `text` is the recognised text with
+    // '?' in place of these glyphs, which are drawn in `glyphs` with '#'
+    // and '.'.
+    UnknownGlyphs { text: String, glyphs: Vec<String> },
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OcrError::UnsupportedHeight(h) => write!(f, "letters must be 6 or 10 rows high, found {} rows", h),
+            OcrError::UnknownGlyphs { text, glyphs } => {
+                write!(f, "unknown glyphs in {:?}:", text)?;
+                for g in glyphs {
+                    write!(f, "\n{}", g)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+// Reads the letters drawn in the rectangle between `top_left` and
+// `bottom_right`, where `marked` tells which positions are part of a
+// letter. Letters are read at the fixed pitch of their font (5 columns for
+// the small font and 8 for the large one) when the marked columns fit it,
+// and are otherwise separated by columns without marked positions.
+fn ocr<F>( top_left: Pos, bottom_right: Pos, marked: F ) -> Result<String, OcrError>
+where
+    F: Fn(&Pos) -> bool,
+{
+    let marked_rows: Vec<_> = (top_left.y..=bottom_right.y)
+        .filter(|y| (top_left.x..=bottom_right.x).any(|x| marked(&Pos { x, y: *y })))
+        .collect();
+    let (y0, y1) = match (marked_rows.first(), marked_rows.last()) {
+        (Some(y0), Some(y1)) if y1 - y0 == 5 || y1 - y0 == 9 => (*y0, *y1),
+        (Some(y0), Some(y1)) => return Err(OcrError::UnsupportedHeight((y1 - y0 + 1) as usize)),
+        _ => return Err(OcrError::UnsupportedHeight(0)),
+    };
+
+    // Draws the columns from x0 to x1 with '#' and '.', one row per line.
+    let draw = |x0: i16, x1: i16| -> Vec<String> {
+        (y0..=y1)
+            .map(|y| (x0..=x1).map(|x| if marked(&Pos { x, y }) { '#' } else { '.' }).collect())
+            .collect()
+    };
+
+    // Removes the blank columns on both sides of a glyph.
+    fn trim<S: AsRef<str>>( rows: &[S] ) -> Vec<String> {
+        let width = rows[0].as_ref().len();
+        let filled = |i: &usize| rows.iter().any(|r| r.as_ref().as_bytes()[*i] == b'#');
+        match ((0..width).find(filled), (0..width).rfind(filled)) {
+            (Some(first), Some(last)) => rows.iter().map(|r| r.as_ref()[first..=last].to_string()).collect(),
+            _ => rows.iter().map(|r| r.as_ref().to_string()).collect(),
+        }
+    }
+
+    let blank = |x: i16| (y0..=y1).all(|y| !marked(&Pos { x, y }));
+    let x_first = (top_left.x..=bottom_right.x).find(|x| !blank(*x)).unwrap();
+    let x_last = (top_left.x..=bottom_right.x).rfind(|x| !blank(*x)).unwrap();
+    let (pitch, gap) = if y1 - y0 == 5 { (5, 1) } else { (8, 2) };
+
+    let mut segments = vec![];
+    if (x_last - x_first + 1 + gap) % pitch == 0 {
+        for x in (x_first..=x_last).step_by(pitch as usize) {
+            segments.push((x, (x + pitch - 1).min(x_last)));
+        }
+    } else {
+        let mut x = x_first;
+        while x <= x_last {
+            if blank(x) {
+                x += 1;
+                continue;
+            }
+            let x0 = x;
+            while x <= x_last && !blank(x) {
+                x += 1;
+            }
+            segments.push((x0, x - 1));
+        }
+    }
+
+    let mut text = String::new();
+    let mut unknown = vec![];
+    for (x0, x1) in segments {
+        // Compare with the letters of the font, without their blank columns.
+        let glyph = trim(&draw(x0, x1));
+        let letter = if y1 - y0 == 5 {
+            FONT_6.iter().find(|(_, rows)| trim(rows) == glyph).map(|(c, _)| *c)
+        } else {
+            FONT_10.iter().find(|(_, rows)| trim(rows) == glyph).map(|(c, _)| *c)
+        };
+        match letter {
+            Some(c) => text.push(c),
+            None => {
+                text.push('?');
+                unknown.push(glyph.join("\n"));
+            }
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::UnknownGlyphs { text, glyphs: unknown })
+    }
+}
+
+// How to combine two values that end up in the same position of a
+// SparseGrid: keep the one already there, overwrite it with the new one,
+// or compute the result from the old and the new one.
+pub enum Collision<'a, T = char> {
+    Keep,
+    Overwrite,
+    Merge(&'a dyn Fn(&T, &T) -> T),
+}
+
+// A sparse grid of values of type `T` (Unicode characters by default),
+// with values accessed by Pos with get()/insert()/remove().
+// The bounding box of the positions is kept up to date on insertion, and
+// recomputed lazily after removals from its edges. Optionally, the grid can
+// keep an index of the positions of each value.
+#[derive(Clone, Debug)]
+pub struct SparseGrid<T = char> {
+    symbols: HashMap<Pos, T>,
+    bounds: OnceLock<Option<(Pos, Pos)>>,
+    index: Option<PosIndex<T>>,
+}
+
+// The positions of each value of a SparseGrid, with the functions that
+// update them. These are created by enable_index(), which requires values
+// that can be hashed, so that the other methods don't.
+#[derive(Clone, Debug)]
+struct PosIndex<T> {
+    positions: HashMap<T, HashSet<Pos>>,
+    add: fn(&mut HashMap<T, HashSet<Pos>>, Pos, &T),
+    remove: fn(&mut HashMap<T, HashSet<Pos>>, &Pos, &T),
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        SparseGrid { symbols: HashMap::new(), bounds: OnceLock::new(), index: None }
+    }
+
+    // The top left and bottom right corners of the rectangle containing
+    // all the positions, or None if the grid is empty.
+    pub fn bounds( &self ) -> Option<(Pos, Pos)> {
+        *self.bounds.get_or_init(|| {
+            let mut positions = self.symbols.keys();
+            positions.next().map(|first| {
+                positions.fold((*first, *first), |(min, max), p| (
+                    Pos { x: min.x.min(p.x), y: min.y.min(p.y) },
+                    Pos { x: max.x.max(p.x), y: max.y.max(p.y) },
+                ))
+            })
+        })
+    }
+
+    pub fn x_min( &self ) -> i16 {
+        self.bounds().map(|(min, _)| min.x).unwrap_or(0)
+    }
+
+    pub fn x_max( &self ) -> i16 {
+        self.bounds().map(|(_, max)| max.x).unwrap_or(0)
+    }
+
+    pub fn y_min( &self ) -> i16 {
+        self.bounds().map(|(min, _)| min.y).unwrap_or(0)
+    }
+
+    pub fn y_max( &self ) -> i16 {
+        self.bounds().map(|(_, max)| max.y).unwrap_or(0)
+    }
+
+    pub fn get(&self, p: &Pos) -> Option<&T> {
+        self.symbols.get(p)
+    }
+
+    pub fn len( &self ) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.symbols.is_empty()
+    }
+
+    // Tells if the grid keeps an index of the positions of each value
+    // (see enable_index()).
+    pub fn is_indexed( &self ) -> bool {
+        self.index.is_some()
+    }
+
+    pub fn entries( &self ) -> impl Iterator<Item = (&Pos, &T)> {
+        self.symbols.iter()
+    }
+
+    // The rectangle containing all the positions (see bounds()).
+    pub fn bounds_rect( &self ) -> Option<Rect> {
+        self.bounds().map(|(min, max)| Rect{ min, max })
+    }
+
+    // Iterates over the positions inside the rectangle `r`, with their
+    // values, in arbitrary order.
+    pub fn entries_rect<'a>( &'a self, r: &Rect ) -> impl Iterator<Item = (&'a Pos, &'a T)> + 'a {
+        let r = *r;
+        self.entries().filter(move |(p, _)| r.contains(p))
+    }
+
+    // Iterates over the positions reached from `p` going in each of the
+    // directions in `dirs` that hold a value, with their values.
+    pub fn neighbors_with<'a>( &'a self, p: &Pos, dirs: &'a [Dir] ) -> impl Iterator<Item = (Pos, &'a T)> + 'a {
+        let p = *p;
+        dirs.iter()
+            .map(move |d| p.go(*d))
+            .filter_map(|n| self.get(&n).map(|v| (n, v)))
+    }
+
+    pub fn neighbors4( &self, p: &Pos ) -> impl Iterator<Item = (Pos, &T)> {
+        self.neighbors_with(p, &consts::NEIGHBORS4)
+    }
+
+    pub fn neighbors8( &self, p: &Pos ) -> impl Iterator<Item = (Pos, &T)> {
+        self.neighbors_with(p, &consts::NEIGHBORS8)
+    }
+
+    // Reads the letters (in one of the Advent of Code fonts) drawn with
+    // the positions that hold a value for which `marked` is true.
+    pub fn ocr_with<F>( &self, marked: F ) -> Result<String, OcrError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let top_left = Pos { x: self.x_min(), y: self.y_min() };
+        let bottom_right = Pos { x: self.x_max(), y: self.y_max() };
+        ocr(top_left, bottom_right, |p| self.get(p).filter(|v| marked(v)).is_some())
+    }
+
+    // Returns the value at the given row/column of the rectangle
+    // containing all the positions.
+    fn get_in_bounds( &self, row: usize, col: usize ) -> Option<&T> {
+        self.get(&Pos { x: self.x_min() + col as i16, y: self.y_min() + row as i16 })
+    }
+
+    fn bounds_size( &self ) -> (usize, usize) {
+        ((self.y_max() - self.y_min() + 1) as usize, (self.x_max() - self.x_min() + 1) as usize)
+    }
+
+    // Writes the rectangle containing all the positions as a PBM image,
+    // with black pixels where `black` is true.
+    pub fn write_pbm<W, F>( &self, w: &mut W, black: F ) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(Option<&T>) -> bool,
+    {
+        let (rows, cols) = self.bounds_size();
+        image::write_pbm(w, rows, cols, |r, c| black(self.get_in_bounds(r, c)))
+    }
+
+    // Writes the rectangle containing all the positions as a PGM image,
+    // with gray levels given by `gray`.
+    pub fn write_pgm<W, F>( &self, w: &mut W, gray: F ) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(Option<&T>) -> u8,
+    {
+        let (rows, cols) = self.bounds_size();
+        image::write_pgm(w, rows, cols, |r, c| gray(self.get_in_bounds(r, c)))
+    }
+
+    // Writes the rectangle containing all the positions as a PPM image,
+    // with colours given by `color`.
+    pub fn write_ppm<W, F>( &self, w: &mut W, color: F ) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(Option<&T>) -> Rgb,
+    {
+        let (rows, cols) = self.bounds_size();
+        image::write_ppm(w, rows, cols, |r, c| color(self.get_in_bounds(r, c)))
+    }
+
+    // Writes the rectangle containing all the positions as an SVG image,
+    // with squares of side `cell_size` coloured by `color` (transparent
+    // where it returns None).
+    pub fn write_svg<W, F>( &self, w: &mut W, cell_size: usize, color: F ) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(Option<&T>) -> Option<Rgb>,
+    {
+        let (rows, cols) = self.bounds_size();
+        image::write_svg(w, rows, cols, cell_size, |r, c| color(self.get_in_bounds(r, c)))
+    }
+
+    // Displays the rectangle containing all the positions, rendering each
+    // position with `f`.
+    pub fn display_with<'a, D, F>( &'a self, f: F ) -> impl Display + 'a
+    where
+        D: Display,
+        F: Fn(Option<&T>) -> D + 'a,
+    {
+        SparseGridDisplay { grid: self, render: f }
+    }
+}
+
+impl<T: Clone> SparseGrid<T> {
+    // An empty grid, with an index if this grid has one.
+    fn empty_like( &self ) -> Self {
+        let mut grid = SparseGrid::new();
+        grid.index = self.index.as_ref().map(|index| PosIndex { positions: HashMap::new(), ..*index });
+        grid
+    }
+
+    pub fn insert( &mut self, p: Pos, v: T ) -> Option<T> {
+        if let Some(bounds) = self.bounds.get_mut() {
+            *bounds = Some(match *bounds {
+                Some((min, max)) => (
+                    Pos { x: min.x.min(p.x), y: min.y.min(p.y) },
+                    Pos { x: max.x.max(p.x), y: max.y.max(p.y) },
+                ),
+                None => (p, p),
+            });
+        }
+        let old = self.symbols.insert(p, v);
+        if let Some(index) = &mut self.index {
+            if let Some(old) = &old {
+                (index.remove)(&mut index.positions, &p, old);
+            }
+            (index.add)(&mut index.positions, p, &self.symbols[&p]);
+        }
+        old
+    }
+
+    pub fn remove( &mut self, p: &Pos ) -> Option<T> {
+        let old = self.symbols.remove(p)?;
+        if let Some(Some((min, max))) = self.bounds.get() {
+            if p.x == min.x || p.y == min.y || p.x == max.x || p.y == max.y {
+                self.bounds.take();
+            }
+        }
+        if let Some(index) = &mut self.index {
+            (index.remove)(&mut index.positions, p, &old);
+        }
+        Some(old)
+    }
+
+
+    // Calls `f` on each of the positions reached from `p` going in each of
+    // the directions in `dirs` that hold a value, with a mutable reference
+    // to their value.
+    pub fn update_neighbors_with<F>( &mut self, p: &Pos, dirs: &[Dir], mut f: F )
+    where
+        F: FnMut(Pos, &mut T),
+    {
+        for d in dirs {
+            let n = p.go(*d);
+            if let Some(v) = self.symbols.get(&n) {
+                let mut v = v.clone();
+                f(n, &mut v);
+                self.insert(n, v);
+            }
+        }
+    }
+
+    // Sets the value `v` at the positions on the segment from `from` to
+    // `to`.
+    pub fn draw_line( &mut self, from: &Pos, to: &Pos, v: T ) {
+        self.draw_line_with(from, to, |_| v.clone());
+    }
+
+    // Sets the values at the positions on the segment from `from` to `to`
+    // computing them with `f` from the current ones.
+    pub fn draw_line_with<F>( &mut self, from: &Pos, to: &Pos, mut f: F )
+    where
+        F: FnMut(Option<&T>) -> T,
+    {
+        for p in from.line_to(to) {
+            let v = f(self.symbols.get(&p));
+            self.insert(p, v);
+        }
+    }
+
+    // Returns a copy of the grid with each position moved by `f`, which
+    // should never move two positions to the same place.
+    fn map_positions<F: Fn(&Pos) -> Pos>( &self, f: F ) -> Self {
+        let mut grid = self.empty_like();
+        for (p, v) in &self.symbols {
+            grid.insert(f(p), v.clone());
+        }
+        grid
+    }
+
+    // Returns a copy of the part of the grid inside the rectangle `r`.
+    pub fn crop_rect( &self, r: &Rect ) -> Self {
+        let mut grid = self.empty_like();
+        for (p, v) in self.entries_rect(r) {
+            grid.insert(*p, v.clone());
+        }
+        grid
+    }
+
+    // Returns a copy of the grid moved in direction `d`.
+    pub fn translate( &self, d: Dir ) -> Self {
+        self.map_positions(|p| p.go(d))
+    }
+
+    // Returns a copy of the grid mirrored across the vertical line at `x`.
+    pub fn reflect_x( &self, x: i16 ) -> Self {
+        self.map_positions(|p| Pos { x: 2 * x - p.x, y: p.y })
+    }
+
+    // Returns a copy of the grid mirrored across the horizontal line at `y`.
+    pub fn reflect_y( &self, y: i16 ) -> Self {
+        self.map_positions(|p| Pos { x: p.x, y: 2 * y - p.y })
+    }
+
+    // Adds the values of `other` to the grid, resolving the positions that
+    // are in both grids according to `collision`.
+    pub fn merge( &mut self, other: &Self, collision: &Collision<T> ) {
+        for (p, v) in &other.symbols {
+            let v = match (self.symbols.get(p), collision) {
+                (None, _) => v.clone(),
+                (Some(_), Collision::Keep) => continue,
+                (Some(_), Collision::Overwrite) => v.clone(),
+                (Some(e), Collision::Merge(f)) => f(e, v),
+            };
+            self.insert(*p, v);
+        }
+    }
+
+    // Folds the part of the grid to the right of the vertical line at `x`
+    // over the left part, dropping the values on the line. If the folded
+    // part extends past the left edge, the grid is moved so that the
+    // smallest x is 0.
+    pub fn fold_x( &mut self, x: i16, collision: &Collision<T> ) {
+        let mut right = self.empty_like();
+        let old = std::mem::replace(self, self.empty_like());
+        for (p, v) in old.symbols {
+            if p.x < x {
+                self.insert(p, v);
+            } else if p.x > x {
+                right.insert(p, v);
+            }
+        }
+        self.merge(&right.reflect_x(x), collision);
+
+        let x_min = self.x_min();
+        if x_min < 0 {
+            *self = self.translate(Dir { dx: -x_min, dy: 0 });
+        }
+    }
+
+    // Folds the part of the grid below the horizontal line at `y` over the
+    // upper part, dropping the values on the line. If the folded part
+    // extends past the top edge, the grid is moved so that the smallest y
+    // is 0.
+    pub fn fold_y( &mut self, y: i16, collision: &Collision<T> ) {
+        let mut bottom = self.empty_like();
+        let old = std::mem::replace(self, self.empty_like());
+        for (p, v) in old.symbols {
+            if p.y < y {
+                self.insert(p, v);
+            } else if p.y > y {
+                bottom.insert(p, v);
+            }
+        }
+        self.merge(&bottom.reflect_y(y), collision);
+
+        let y_min = self.y_min();
+        if y_min < 0 {
+            *self = self.translate(Dir { dx: 0, dy: -y_min });
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> SparseGrid<T> {
+    // Starts keeping an index of the positions of each value, used by
+    // find() and positions_of().
+    pub fn enable_index( &mut self ) {
+        fn add<T: Clone + Eq + Hash>( positions: &mut HashMap<T, HashSet<Pos>>, p: Pos, v: &T ) {
+            positions.entry(v.clone()).or_default().insert(p);
+        }
+
+        fn remove<T: Eq + Hash>( positions: &mut HashMap<T, HashSet<Pos>>, p: &Pos, v: &T ) {
+            if let Some(ps) = positions.get_mut(v) {
+                ps.remove(p);
+                if ps.is_empty() {
+                    positions.remove(v);
+                }
+            }
+        }
+
+        let mut positions = HashMap::new();
+        for (p, v) in &self.symbols {
+            add(&mut positions, *p, v);
+        }
+        self.index = Some(PosIndex { positions, add, remove });
+    }
+
+    // Returns one of the positions holding `v`, using the index if there
+    // is one.
+    pub fn find( &self, v: &T ) -> Option<&Pos> {
+        match &self.index {
+            Some(index) => index.positions.get(v).and_then(|positions| positions.iter().next()),
+            None => self.symbols.iter().find(|(_, sym)| *sym == v).map(|(pos, _)| pos),
+        }
+    }
+
+    // Iterates over the positions holding `v`, using the index if there
+    // is one.
+    pub fn positions_of<'a>( &'a self, v: &'a T ) -> Box<dyn Iterator<Item = &'a Pos> + 'a> {
+        match &self.index {
+            Some(index) => Box::new(index.positions.get(v).into_iter().flatten()),
+            None => Box::new(self.symbols.iter().filter(move |(_, sym)| *sym == v).map(|(pos, _)| pos)),
+        }
+    }
+}
+
+
+impl SparseGrid<char> {
+    pub fn create_from( s: &str ) -> Self {
+        SparseGrid::parse(s).unwrap()
+    }
+
+    // Parses a sparse grid from text, with one row per line, skipping
+    // whitespace.
+    pub fn parse( s: &str ) -> Result<Self, ParseGridError> {
+        let mut grid = SparseGrid::new();
+        for (y, line) in s.lines().enumerate() {
+            if y >= i16::MAX as usize {
+                return Err(ParseGridError{ row: y, col: 0, kind: ParseGridErrorKind::TooLarge });
+            }
+            for (x, c) in line.chars().enumerate() {
+                if x >= i16::MAX as usize {
+                    return Err(ParseGridError{ row: y, col: x, kind: ParseGridErrorKind::TooLarge });
+                }
+                if c.is_control() && c != '\t' {
+                    return Err(ParseGridError{ row: y, col: x, kind: ParseGridErrorKind::InvalidChar(c) });
+                }
+                if !c.is_ascii_whitespace() {
+                    grid.insert(Pos { x: x as i16, y: y as i16 }, c);
+                }
+            }
+        }
+        Ok(grid)
+    }
+
+    // Reads the letters (in one of the Advent of Code fonts) drawn with
+    // any symbol other than '.'.
+    pub fn ocr( &self ) -> Result<String, OcrError> {
+        self.ocr_with(|c| *c != '.')
+    }
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for SparseGrid<char> {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        write!(f, "{}", self.display_with(|c| *c.unwrap_or(&' ')))
+    }
+}
+
+struct SparseGridDisplay<'a, T, F> {
+    grid: &'a SparseGrid<T>,
+    render: F,
+}
+
+impl<T, D: Display, F: Fn(Option<&T>) -> D> Display for SparseGridDisplay<'_, T, F> {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        let x0 = self.grid.x_min();
+        let x1 = self.grid.x_max();
+        let y0 = self.grid.y_min();
+        let y1 = self.grid.y_max();
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                write!(f, "{}", (self.render)(self.grid.get(&Pos { x, y })))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::consts::*;
+
+    #[test]
+    fn lines() {
+        let mut g = SparseGrid::new();
+        g.draw_line(&Pos{ x: 0, y: 0 }, &Pos{ x: 5, y: 2 }, '#');
+        g.draw_line_with(&Pos{ x: 2, y: 2 }, &Pos{ x: 4, y: 0 }, |c| if c.is_some() { 'X' } else { 'o' });
+        assert_eq!(g.to_string(), "##  o \n  #X  \n  o ##\n");
+        assert_eq!(Pos{ x: 2, y: 1 }.line_to(&Pos{ x: 2, y: 1 }).collect::<Vec<_>>(), vec![Pos{ x: 2, y: 1 }]);
+
+        let mut g = SimpleGrid::<u8>::new(3, 4);
+        g.draw_line_with(&Pos{ x: -1, y: 0 }, &Pos{ x: 3, y: 0 }, |v| *v += 1);
+        g.draw_line_with(&Pos{ x: 3, y: 2 }, &Pos{ x: 1, y: 0 }, |v| *v += 1);
+        assert_eq!(g.display().to_string(), "1211\n0010\n0001\n");
+        g.set_wrapping(true);
+        g.draw_line(&Pos{ x: 2, y: 1 }, &Pos{ x: 5, y: 1 }, 7);
+        assert_eq!(g.display().to_string(), "1211\n0077\n0001\n");
+    }
+
+    #[test]
+    fn folds() {
+        let mut g = SparseGrid::create_from("a b c\n");
+        g.fold_x(2, &Collision::Keep);
+        assert_eq!(g.to_string(), "a\n");
+
+        let mut g = SparseGrid::create_from("a b c\n");
+        g.fold_x(2, &Collision::Overwrite);
+        assert_eq!(g.to_string(), "c\n");
+
+        let mut g = SparseGrid::create_from("a b    c\n");
+        g.fold_x(2, &Collision::Keep);
+        assert_eq!(g.to_string(), "c  a\n");
+        assert_eq!(g.x_min(), 0);
+
+        let mut g = SparseGrid::create_from("1\n2\n3\n4\n5\n");
+        g.fold_y(1, &Collision::Merge(&|a, b| *a.max(b)));
+        assert_eq!(g.to_string(), "5\n4\n3\n");
+        assert_eq!(g.y_min(), 0);
+    }
+
+    #[test]
+    fn ocr() {
+        let g = SparseGrid::create_from("\
+####   ██  ██  █  █
+█       █ █  █ █  █
+███     █ █  █ ████
+█       █ ████ █  █
+█    █  █ █  █ █  █
+█     ██  █  █ █  █
+");
+        assert_eq!(g.ocr(), Ok("FJAH".to_string()));
+
+        let g = SimpleGrid::create_from("\
+#....#..######.
+#....#.......#.
+#....#.......#.
+#....#......#..
+######.....#...
+#....#....#....
+#....#...#.....
+#....#..#......
+#....#..#......
+#....#..######.
+");
+        assert_eq!(g.ocr_with(|c| *c == b'#'), Ok("HZ".to_string()));
+
+        let g = SparseGrid::create_from("#\n#\n#\n#\n#\n#\n");
+        assert_eq!(g.ocr(), Err(OcrError::UnknownGlyphs { text: "?".to_string(), glyphs: vec!["#\n#\n#\n#\n#\n#".to_string()] }));
+        assert_eq!(SparseGrid::create_from("#\n").ocr(), Err(OcrError::UnsupportedHeight(1)));
+
+        // The 'Y' fills its 5 columns, so it touches the next glyph.
+        let g = SimpleGrid::create_from("\
+#...#####
+#...#####
+.#.#.####
+..#..####
+..#..####
+..#..####
+");
+        let block = "####\n####\n####\n####\n####\n####".to_string();
+        assert_eq!(g.ocr_with(|c| *c == b'#'), Err(OcrError::UnknownGlyphs { text: "Y?".to_string(), glyphs: vec![block] }));
+    }
+
+    #[test]
+    fn sparse_grid() {
+        let mut g = SparseGrid::new();
+        g.enable_index();
+        for (x, y, v) in [(0, 0, 1), (3, -2, 2), (-1, 4, 1), (2, 2, 2)] {
+            g.insert(Pos { x, y }, v);
+        }
+        assert_eq!(g.bounds(), Some((Pos { x: -1, y: -2 }, Pos { x: 3, y: 4 })));
+        g.remove(&Pos { x: 3, y: -2 });
+        assert_eq!(g.bounds(), Some((Pos { x: -1, y: 0 }, Pos { x: 2, y: 4 })));
+        // Insertions while the bounds are not known are counted once they
+        // are recomputed.
+        g.remove(&Pos { x: -1, y: 4 });
+        g.insert(Pos { x: 1, y: 5 }, 1);
+        assert_eq!(g.bounds(), Some((Pos { x: 0, y: 0 }, Pos { x: 2, y: 5 })));
+        g.remove(&Pos { x: 1, y: 5 });
+        g.insert(Pos { x: -1, y: 4 }, 1);
+        g.insert(Pos { x: 0, y: 0 }, 2);
+        g.insert(Pos { x: 2, y: 2 }, 2);
+        g.insert(Pos { x: 1, y: 1 }, 3);
+        g.remove(&Pos { x: 1, y: 1 });
+        assert_eq!(g.bounds(), Some((Pos { x: -1, y: 0 }, Pos { x: 2, y: 4 })));
+
+        let mut positions: Vec<_> = g.positions_of(&2).copied().collect();
+        positions.sort_by_key(|p| p.x);
+        assert_eq!(positions, vec![Pos { x: 0, y: 0 }, Pos { x: 2, y: 2 }]);
+        assert_eq!(g.find(&1), Some(&Pos { x: -1, y: 4 }));
+        assert_eq!(g.find(&3), None);
+        assert_eq!(g.display_with(|v| v.map(|v| v.to_string()).unwrap_or(".".to_string())).to_string(), "\
+.2..
+....
+...2
+....
+1...
+");
+
+        // Values don't need to be hashable without an index, and the grid
+        // can be shared between threads.
+        let mut g = SparseGrid::new();
+        g.insert(Pos { x: 1, y: 2 }, 0.5);
+        g.draw_line(&Pos { x: 0, y: 0 }, &Pos { x: 2, y: 0 }, 1.5);
+        assert_eq!(g.remove(&Pos { x: 1, y: 2 }), Some(0.5));
+        assert_eq!(g.bounds(), Some((Pos { x: 0, y: 0 }, Pos { x: 2, y: 0 })));
+        fn shared<T: Sync>( _: &T ) {}
+        shared(&g);
+    }
+
+    #[test]
+    fn images() {
+        let mut w = vec![];
+        SimpleGrid::create_from("#.\n.#\n").write_pbm(&mut w, |c| *c == b'#').unwrap();
+        assert_eq!(w, b"P4\n2 2\n\x80\x40");
+
+        let mut w = vec![];
+        SparseGrid::create_from(" a\n  b\n").write_ppm(&mut w, |c| match c {
+            Some('a') => [255, 0, 0],
+            Some(_) => [0, 0, 255],
+            None => [0, 0, 0],
+        }).unwrap();
+        assert_eq!(w, b"P6\n2 2\n255\n\xff\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn ansi() {
+        let g = SimpleGrid::create_from_with("19\n", |c| c.to_digit(10).unwrap() as u8);
+        let render = |v: &u8| ((b'0' + v) as char, Some(image::heatmap(*v as i64, 1, 9)));
+        let highlight = HashSet::from([Pos{ x: 1, y: 0 }]);
+        assert_eq!(g.display_ansi(render, &highlight, false).to_string(), "19\n");
+        assert_eq!(g.display_ansi(render, &highlight, true).to_string(), "\x1b[38;2;0;0;255m1\x1b[0m\x1b[38;2;255;0;0m\x1b[7m9\x1b[0m\n");
+    }
+
+    #[test]
+    fn rays() {
+        let mut g = SimpleGrid::create_from("\
+#...#
+..#..
+.#.##
+.....
+");
+        let p = Pos{ x: 2, y: 2 };
+        let ray: Vec<_> = g.ray(&p, UP_RIGHT).map(|(p, v)| (p, *v as char)).collect();
+        assert_eq!(ray, [(Pos{ x: 3, y: 1 }, '.'), (Pos{ x: 4, y: 0 }, '#')]);
+        assert_eq!(g.ray_until(&p, RIGHT, |v| *v == b'#').count(), 0);
+        assert_eq!(g.ray_until(&p, DOWN, |v| *v == b'#').count(), 1);
+        assert_eq!(g.first_hit(&p, UP_LEFT, |v| *v == b'#'), Some((Pos{ x: 0, y: 0 }, &b'#')));
+        assert_eq!(g.first_hit(&p, DOWN, |v| *v == b'#'), None);
+
+        let mut visible: Vec<_> = g.visible_from(&p, |v| *v == b'#').map(|(p, _)| p).collect();
+        visible.sort_by_key(|p| (p.y, p.x));
+        assert_eq!(visible, [
+            Pos{ x: 0, y: 0 }, Pos{ x: 4, y: 0 }, Pos{ x: 2, y: 1 }, Pos{ x: 1, y: 2 }, Pos{ x: 3, y: 2 },
+        ]);
+
+        g.set_wrapping(true);
+        assert_eq!(g.ray(&p, RIGHT).map(|(p, _)| p.x).collect::<Vec<_>>(), [3, 4, 0, 1]);
+        assert_eq!(g.first_hit(&p, DOWN, |v| *v == b'#'), Some((Pos{ x: 2, y: 1 }, &b'#')));
+    }
+
+    #[test]
+    fn rects() {
+        let r: Rect = "x=1..3, y=-2..0".parse().unwrap();
+        assert_eq!(r, Rect::new(Pos{ x: 3, y: 0 }, Pos{ x: 1, y: -2 }));
+        assert_eq!((r.width(), r.height(), r.area()), (3, 3, 9));
+        assert!(r.contains(&Pos{ x: 1, y: -1 }) && !r.contains(&Pos{ x: 0, y: -1 }));
+        assert_eq!(r.corners().collect::<Vec<_>>(), [
+            Pos{ x: 1, y: -2 }, Pos{ x: 3, y: -2 }, Pos{ x: 3, y: 0 }, Pos{ x: 1, y: 0 },
+        ]);
+
+        let s = Rect::new(Pos{ x: 3, y: 0 }, Pos{ x: 5, y: 4 });
+        assert_eq!(r.intersect(&s), Some(Rect::new(Pos{ x: 3, y: 0 }, Pos{ x: 3, y: 0 })));
+        assert_eq!(r.intersect(&Rect::new(ORIGIN, ORIGIN)), None);
+        assert_eq!(r.union(&s), Rect::new(Pos{ x: 1, y: -2 }, Pos{ x: 5, y: 4 }));
+
+        assert_eq!("x=5, y=1..2".parse(), Ok(Rect::<i64>::new(Pos{ x: 5, y: 1 }, Pos{ x: 5, y: 2 })));
+        assert_eq!("x=1..2".parse::<Rect>(), Err(ParseRangesError("x=1..2".to_string())));
+        assert_eq!("x=1..2, z=3".parse::<Rect>(), Err(ParseRangesError("z=3".to_string())));
+        assert_eq!("x=1..a, y=3".parse::<Rect>(), Err(ParseRangesError("x=1..a".to_string())));
+
+        let g = SimpleGrid::create_from("abc\ndef\nghi\n");
+        let r = Rect::new(Pos{ x: 1, y: 1 }, Pos{ x: 5, y: -3 });
+        assert_eq!(g.entries_rect(&r).map(|(_, v)| *v).collect::<Vec<_>>(), b"bcef");
+        let r = g.rect().intersect(&r).unwrap();
+        assert_eq!(g.crop_rect(&r).to_string(), "bc\nef\n");
+
+        let mut sg = SparseGrid::parse("#..\n.#.\n..#\n").unwrap();
+        sg = sg.crop_rect(&Rect::new(ORIGIN, Pos{ x: 1, y: 1 }));
+        assert_eq!(sg.bounds_rect(), Some(Rect::new(ORIGIN, Pos{ x: 1, y: 1 })));
+        assert_eq!(sg.entries_rect(&Rect::new(ORIGIN, ORIGIN)).count(), 1);
+    }
+
+    #[test]
+    fn bit_grid() {
+        let g = SimpleGrid::create_from("#..#\n.##.\n");
+        let b = BitGrid::from_grid(&g, |c| *c == b'#');
+        assert_eq!(b.to_string(), "#..#\n.##.\n");
+        assert_eq!(b.count_ones(), 4);
+        assert_eq!(b.positions().collect::<Vec<_>>(), [
+            Pos{ x: 0, y: 0 }, Pos{ x: 3, y: 0 }, Pos{ x: 1, y: 1 }, Pos{ x: 2, y: 1 },
+        ]);
+        assert_eq!(b.shift(RIGHT, false).to_string(), ".#..\n..##\n");
+        assert_eq!(b.shift(DOWN_RIGHT, true).to_string(), "..##\n##..\n");
+        assert_eq!(b.shift(LEFT * 3, true), b.shift(RIGHT, true));
+        assert_eq!((!&b).to_string(), ".##.\n#..#\n");
+        assert_eq!((&b & &b.shift(LEFT, false)).to_string(), "....\n.#..\n");
+        assert_eq!((&b | &b.shift(UP, false)).count_ones(), 6);
+        assert!(!(&b ^ &b).any());
+
+        // Shifts across word boundaries.
+        let mut wide = BitGrid::new(2, 130);
+        wide.set(0, 63, true);
+        wide.set(1, 129, true);
+        let moved = wide.shift(RIGHT * 2, true);
+        assert_eq!(moved.positions().collect::<Vec<_>>(), [Pos{ x: 65, y: 0 }, Pos{ x: 1, y: 1 }]);
+        assert_eq!(moved.shift(LEFT * 2, false).positions().collect::<Vec<_>>(), [Pos{ x: 63, y: 0 }]);
+        assert_eq!((!&wide).count_ones(), 2 * 130 - 2);
+    }
+
+    #[test]
+    fn distance_field() {
+        let g = SimpleGrid::create_from("\
+.....
+.###.
+.#...
+.....
+");
+        let sources = [Pos{ x: 4, y: 3 }, Pos{ x: 0, y: 0 }, Pos{ x: 2, y: 2 }];
+        let (distances, labels) = g.distance_field(&sources, &NEIGHBORS4, |c| *c == b'.');
+        let show = |g: &SimpleGrid<Option<usize>>| g.display_with(|v| v.map_or('#', |v| (b'0' + v as u8) as char)).to_string();
+        assert_eq!(show(&distances), "\
+01233
+1###2
+2#011
+32110
+");
+        assert_eq!(show(&labels), "\
+11110
+1###0
+1#220
+12200
+");
+
+        let mut g = SimpleGrid::create_from("..#..\n");
+        assert_eq!(show(&g.distance_field(&[ORIGIN], &NEIGHBORS4, |c| *c == b'.').0), "01###\n");
+        g.set_wrapping(true);
+        assert_eq!(show(&g.distance_field(&[ORIGIN], &NEIGHBORS4, |c| *c == b'.').0), "01#21\n");
+    }
+
+    #[test]
+    fn parallel() {
+        let g = SimpleGrid::from_fn(7, 5, |r, c| (r * 5 + c) as u32);
+        let mut sums = SimpleGrid::new(7, 5);
+        g.stencil_into(3, &Edge::Wrap, &mut sums, |_, w| w.iter().copied().sum::<u32>());
+        for threads in [1, 2, 3, 7, 16] {
+            let mut par_sums = SimpleGrid::new(7, 5);
+            g.par_stencil_into(3, &Edge::Wrap, &mut par_sums, threads, |_, w| w.iter().copied().sum::<u32>());
+            assert_eq!(par_sums, sums);
+
+            let mut labels = SimpleGrid::filled(7, 5, (ORIGIN, 0));
+            g.par_map_into(&mut labels, threads, |p, v| (p, v * 2));
+            assert!(labels.entries_pos().all(|(p, (q, v))| p == *q && *v == 2 * g.get_pos(&p).unwrap()));
+        }
+    }
+
+    #[test]
+    fn neighbors() {
+        let mut g = SimpleGrid::from_fn(3, 4, |r, c| (r * 4 + c) as u8);
+        let values = |it: &mut dyn Iterator<Item = (Pos, &u8)>| {
+            let mut v: Vec<_> = it.map(|(_, v)| *v).collect();
+            v.sort();
+            v
+        };
+        assert_eq!(values(&mut g.neighbors4(&ORIGIN)), [1, 4]);
+        assert_eq!(values(&mut g.neighbors8(&ORIGIN)), [1, 4, 5]);
+        assert_eq!(values(&mut g.neighbors4(&Pos{ x: 3, y: 1 })), [3, 6, 11]);
+        assert_eq!(values(&mut g.neighbors8(&Pos{ x: 3, y: 2 })), [6, 7, 10]);
+        assert_eq!(values(&mut g.neighbors8(&Pos{ x: 1, y: 1 })), [0, 1, 2, 4, 6, 8, 9, 10]);
+
+        g.update_neighbors4(&Pos{ x: 3, y: 0 }, |_, v| *v += 100);
+        g.update_neighbors8(&Pos{ x: 0, y: 2 }, |_, v| *v += 100);
+        assert_eq!(g.values().filter(|v| **v >= 100).count(), 5);
+        assert_eq!((g.get(0, 2), g.get(1, 3), g.get(1, 1)), (Some(&102), Some(&107), Some(&105)));
+
+        g.set_wrapping(true);
+        assert_eq!(values(&mut g.neighbors4(&ORIGIN)), [1, 3, 8, 104]);
+
+        let mut s = SparseGrid::parse("a.b\n...\nc..\n").unwrap();
+        s.remove(&Pos{ x: 1, y: 0 });
+        s.remove(&Pos{ x: 1, y: 1 });
+        let mut n: Vec<_> = s.neighbors8(&Pos{ x: 1, y: 1 }).map(|(_, v)| *v).collect();
+        n.sort();
+        assert_eq!(n, ['.', '.', '.', '.', 'a', 'b', 'c']);
+        assert_eq!(s.neighbors4(&Pos{ x: 1, y: 1 }).count(), 3);
+        assert_eq!(s.neighbors4(&Pos{ x: 0, y: 0 }).map(|(p, _)| p).collect::<Vec<_>>(), [Pos{ x: 0, y: 1 }]);
+        assert_eq!(s.neighbors8(&Pos{ x: -5, y: -5 }).count(), 0);
+
+        s.update_neighbors_with(&Pos{ x: 1, y: 1 }, &NEIGHBORS4, |_, v| *v = '#');
+        s.update_neighbors_with(&Pos{ x: 2, y: 2 }, &NEIGHBORS8, |_, v| *v = '+');
+        assert_eq!(s.get(&Pos{ x: 1, y: 0 }), None);
+        assert_eq!(s.get(&Pos{ x: 1, y: 1 }), None);
+        assert_eq!(s.len(), 7);
+        assert_eq!(s.to_string(), "a b\n# +\nc+.\n");
+    }
+
+    #[test]
+    fn tiled_grid() {
+        // Each tile adds 10 per tile row and 100 per tile column.
+        let base = SimpleGrid::from_fn(2, 3, |r, c| (r * 3 + c) as u32);
+        let tiled = TiledGrid::new(&base, 3, 2, |v, tr, tc| v + 10 * tr as u32 + 100 * tc as u32);
+        assert_eq!((tiled.rows(), tiled.cols()), (6, 6));
+        assert_eq!(tiled.bottom_right(), Pos{ x: 5, y: 5 });
+
+        // Both sides of the tile boundaries.
+        assert_eq!(tiled.get(1, 2), Some(5));
+        assert_eq!(tiled.get(1, 3), Some(103));
+        assert_eq!(tiled.get(2, 2), Some(12));
+        assert_eq!(tiled.get_pos(&Pos{ x: 3, y: 2 }), Some(110));
+
+        // The last tile and just past it.
+        assert_eq!(tiled.get(5, 5), Some(5 + 20 + 100));
+        assert_eq!(tiled.get(6, 5), None);
+        assert_eq!(tiled.get(5, 6), None);
+        assert_eq!(tiled.get_pos(&Pos{ x: -1, y: 0 }), None);
+
+        let mut n: Vec<_> = tiled.neighbors8(&Pos{ x: 5, y: 5 }).map(|(_, v)| v).collect();
+        n.sort();
+        assert_eq!(n, [121, 122, 124]);
+        assert_eq!(tiled.values().count(), 36);
+        assert_eq!(tiled.to_grid().get(4, 3), Some(&120));
+    }
+
+    #[test]
+    fn operators() {
+        let p = Pos{ x: 1, y: 2 };
+        assert_eq!(p + RIGHT * 3, Pos{ x: 4, y: 2 });
+        assert_eq!(p - UP, p + DOWN);
+        assert_eq!(Pos{ x: 4, y: 0 } - p, Dir{ dx: 3, dy: -2 });
+        assert_eq!(-(UP + LEFT), DOWN_RIGHT);
+
+        let mut q = p;
+        q += DOWN_RIGHT;
+        q -= RIGHT;
+        assert_eq!(q, Pos{ x: 1, y: 3 });
+
+        let far: Pos<i64> = Pos{ x: 3_000_000_000, y: -4 };
+        assert_eq!(far.distance(&Pos::default()), 3_000_000_004);
+        assert_eq!(far.chebyshev_distance(&Pos{ x: 0, y: 0 }), 3_000_000_000);
+        assert_eq!(Pos{ x: 3, y: 4 }.squared_distance(&ORIGIN), 25);
+    }
+
+    #[test]
+    fn parse() {
+        let g = SimpleGrid::parse("ab\r\ncd").unwrap();
+        assert_eq!(g.to_string(), "ab\ncd\n");
+        assert_eq!(SimpleGrid::parse("abc").unwrap().to_string(), "abc\n");
+        assert_eq!(SimpleGrid::parse("ab\nabc\n").unwrap_err(), ParseGridError{ row: 1, col: 2, kind: ParseGridErrorKind::RaggedLine });
+        assert_eq!(SimpleGrid::parse("\n").unwrap_err().kind, ParseGridErrorKind::Empty);
+        let wide = "#".repeat(40000);
+        assert_eq!(SimpleGrid::parse(&wide).unwrap_err(), ParseGridError{ row: 0, col: 32766, kind: ParseGridErrorKind::TooLarge });
+        assert_eq!(SimpleGrid::parse_with(&wide, Some).unwrap_err(), ParseGridError{ row: 0, col: 32766, kind: ParseGridErrorKind::TooLarge });
+        assert_eq!(SimpleGrid::parse(&"#\n".repeat(40000)).unwrap_err(), ParseGridError{ row: 32766, col: 0, kind: ParseGridErrorKind::TooLarge });
+        assert_eq!(SparseGrid::parse(&wide).unwrap_err(), ParseGridError{ row: 0, col: 32767, kind: ParseGridErrorKind::TooLarge });
+        assert_eq!(
+            SimpleGrid::parse_with("12\n3x\n", |c| c.to_digit(10)).unwrap_err().to_string(),
+            "invalid character 'x' at row 1, column 1",
+        );
+
+        let g = SparseGrid::parse("#.\r\n .#").unwrap();
+        assert_eq!(g.to_string(), "#. \n .#\n");
+        assert_eq!(SparseGrid::parse("#\x07").unwrap_err().kind, ParseGridErrorKind::InvalidChar('\x07'));
+    }
+
+    #[test]
+    fn transforms() {
+        let g = SimpleGrid::create_from("abc\ndef\n");
+        assert_eq!(g.rotate_left().to_string(), "cf\nbe\nad\n");
+        assert_eq!(g.rotate_right().to_string(), "da\neb\nfc\n");
+        assert_eq!(g.transpose().to_string(), "ad\nbe\ncf\n");
+        assert_eq!(g.flip_horizontal().to_string(), "cba\nfed\n");
+        assert_eq!(g.flip_vertical().to_string(), "def\nabc\n");
+        assert_eq!(g.crop(&Pos{ x: 1, y: 0 }, 2, 2).unwrap().to_string(), "bc\nef\n");
+        assert_eq!(g.crop(&Pos{ x: 3, y: 0 }, 2, 0), None);
+        assert_eq!(g.crop(&Pos{ x: 0, y: 1 }, 0, 3), None);
+
+        let view = g.subgrid(&Pos{ x: 1, y: 1 }, 1, 2);
+        assert_eq!(view.get_pos(&Pos{ x: 1, y: 0 }), Some(&b'f'));
+        assert_eq!(view.get(1, 0), None);
+        assert!(view.matches(&SimpleGrid::create_from("ef\n")));
+        assert!(!view.matches(&SimpleGrid::create_from("fe\n")));
+        let copy = view;
+        assert_eq!(copy.to_grid(), view.to_grid());
+    }
+
+    #[test]
+    fn stencil() {
+        let g = SimpleGrid::create_from_with("123\n456\n", |c| c.to_digit(10).unwrap());
+        let sum = |_, w: &[&u32]| w.iter().copied().sum::<u32>();
+        assert_eq!(g.stencil(3, &Edge::Background(0), sum).display_with(|v| format!("{:3}", v)).to_string(), " 12 21 16\n 12 21 16\n");
+        assert_eq!(g.stencil(3, &Edge::Clamp, sum).display_with(|v| format!("{:3}", v)).to_string(), " 21 27 33\n 30 36 42\n");
+        assert_eq!(g.stencil(3, &Edge::Wrap, sum).display_with(|v| format!("{:3}", v)).to_string(), " 36 36 36\n 27 27 27\n");
+        let mut grown = SimpleGrid::new(4, 5);
+        g.par_stencil_grown_into(3, &Edge::Background(0), 1, &mut grown, 2, |_, w| w.iter().copied().sum::<u32>());
+        assert_eq!(grown.display_with(|v| format!("{:3}", v)).to_string(), "  1  3  6  5  3\n  5 12 21 16  9\n  5 12 21 16  9\n  4  9 15 11  6\n");
+        // The middle row has neighbourhoods inside the grid.
+        let g = SimpleGrid::create_from_with("1234\n5678\n9012\n", |c| c.to_digit(10).unwrap());
+        assert_eq!(g.stencil(3, &Edge::Background(0), sum).display_with(|v| format!("{:3}", v)).to_string(), " 14 24 30 22\n 23 34 33 25\n 20 28 24 18\n");
+    }
+
+    #[test]
+    fn regions() {
+        let g = SimpleGrid::create_from("aab\nabb\nbba\n");
+        let (labels, regions) = g.regions(&consts::NEIGHBORS4, |a, b| a == b);
+        assert_eq!(labels.display().to_string(), "001\n011\n112\n");
+        assert_eq!(regions, vec![
+            Region{ size: 3, top_left: Pos{ x: 0, y: 0 }, bottom_right: Pos{ x: 1, y: 1 }, representative: Pos{ x: 0, y: 0 } },
+            Region{ size: 5, top_left: Pos{ x: 0, y: 0 }, bottom_right: Pos{ x: 2, y: 2 }, representative: Pos{ x: 2, y: 0 } },
+            Region{ size: 1, top_left: Pos{ x: 2, y: 2 }, bottom_right: Pos{ x: 2, y: 2 }, representative: Pos{ x: 2, y: 2 } },
+        ]);
+        let mut reached = g.flood_fill(&Pos{ x: 1, y: 0 }, &consts::NEIGHBORS8, |a, b| a == b);
+        reached.sort_by_key(|p| (p.y, p.x));
+        assert_eq!(reached, vec![Pos{ x: 0, y: 0 }, Pos{ x: 1, y: 0 }, Pos{ x: 0, y: 1 }]);
+    }
+
+    #[test]
+    fn infinite_grid() {
+        let mut g = InfiniteGrid::from_grid(SimpleGrid::create_from("...\n.#.\n...\n"), b'.');
+        assert_eq!(g.get(&Pos{ x: -5, y: 7 }), &b'.');
+        g.set(&Pos{ x: -2, y: 1 }, b'#');
+        assert_eq!(g.top_left(), Pos{ x: -2, y: 0 });
+        assert_eq!(g.to_string(), ".....\n#..#.\n.....\n");
+        g.trim();
+        assert_eq!(g.top_left(), Pos{ x: -2, y: 1 });
+        assert_eq!(g.to_string(), "#..#\n");
+
+        let mut tiled = SimpleGrid::create_from("#\n");
+        tiled.set_wrapping(true);
+        let mut g = InfiniteGrid::from_grid(tiled, b'.');
+        assert_eq!(g.get(&Pos{ x: 10, y: 10 }), &b'.');
+        g.grow(1);
+        assert_eq!(g.to_string(), "...\n.#.\n...\n");
+        for v in g.values_mut() {
+            *v = if *v == b'#' { b'.' } else { b'#' };
+        }
+        assert_eq!(g.to_string(), "###\n#.#\n###\n");
+        assert_eq!(g.get(&Pos{ x: 10, y: 10 }), &b'.');
     }
 }
@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+use crate::grid::{parse_ranges, Coord, ParseRangesError};
+
+pub mod consts {
+    use super::{Pos3, Dir3};
+    pub const ORIGIN: Pos3 = Pos3{ x: 0, y: 0, z: 0 };
+    pub const PLUS_X: Dir3 = Dir3{ dx: 1, dy: 0, dz: 0 };
+    pub const MINUS_X: Dir3 = Dir3{ dx: -1, dy: 0, dz: 0 };
+    pub const PLUS_Y: Dir3 = Dir3{ dx: 0, dy: 1, dz: 0 };
+    pub const MINUS_Y: Dir3 = Dir3{ dx: 0, dy: -1, dz: 0 };
+    pub const PLUS_Z: Dir3 = Dir3{ dx: 0, dy: 0, dz: 1 };
+    pub const MINUS_Z: Dir3 = Dir3{ dx: 0, dy: 0, dz: -1 };
+    pub const NEIGHBORS6: [Dir3; 6] = [PLUS_X, MINUS_X, PLUS_Y, MINUS_Y, PLUS_Z, MINUS_Z];
+}
+
+// A position with coordinates of type `C` (see grid::Pos).
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Pos3<C = i16> {
+    pub x: C,
+    pub y: C,
+    pub z: C,
+}
+
+impl<C: Coord> Pos3<C> {
+    pub fn go( &self, dir: Dir3<C> ) -> Pos3<C> {
+        Pos3{ x: self.x + dir.dx, y: self.y + dir.dy, z: self.z + dir.dz }
+    }
+
+    // The Manhattan distance.
+    pub fn distance( &self, other: &Pos3<C> ) -> C {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+
+    pub fn chebyshev_distance( &self, other: &Pos3<C> ) -> C {
+        (self.x - other.x).abs().max((self.y - other.y).abs()).max((self.z - other.z).abs())
+    }
+
+    // The square of the Euclidean distance.
+    pub fn squared_distance( &self, other: &Pos3<C> ) -> C {
+        let d = self.dir_to(other);
+        d.dx * d.dx + d.dy * d.dy + d.dz * d.dz
+    }
+
+    // The direction that leads from this position to `other`.
+    pub fn dir_to( &self, other: &Pos3<C> ) -> Dir3<C> {
+        Dir3{ dx: other.x - self.x, dy: other.y - self.y, dz: other.z - self.z }
+    }
+}
+
+impl<C: Coord> Add<Dir3<C>> for Pos3<C> {
+    type Output = Pos3<C>;
+
+    fn add( self, dir: Dir3<C> ) -> Pos3<C> {
+        self.go(dir)
+    }
+}
+
+impl<C: Coord> Sub<Dir3<C>> for Pos3<C> {
+    type Output = Pos3<C>;
+
+    fn sub( self, dir: Dir3<C> ) -> Pos3<C> {
+        self.go(-dir)
+    }
+}
+
+// The direction that leads from `other` to this position.
+impl<C: Coord> Sub for Pos3<C> {
+    type Output = Dir3<C>;
+
+    fn sub( self, other: Pos3<C> ) -> Dir3<C> {
+        other.dir_to(&self)
+    }
+}
+
+impl<C: Coord> AddAssign<Dir3<C>> for Pos3<C> {
+    fn add_assign( &mut self, dir: Dir3<C> ) {
+        *self = self.go(dir);
+    }
+}
+
+impl<C: Coord> SubAssign<Dir3<C>> for Pos3<C> {
+    fn sub_assign( &mut self, dir: Dir3<C> ) {
+        *self = self.go(-dir);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Dir3<C = i16> {
+    pub dx: C,
+    pub dy: C,
+    pub dz: C,
+}
+
+impl<C: Coord> Dir3<C> {
+    pub fn add( &self, other: &Dir3<C> ) -> Dir3<C> {
+        Dir3{ dx: self.dx + other.dx, dy: self.dy + other.dy, dz: self.dz + other.dz }
+    }
+
+    // Rotations by 90 degrees around each axis, counterclockwise when
+    // looking from the positive side of the axis towards the origin.
+    pub fn rotate_x( &self ) -> Dir3<C> {
+        Dir3{ dx: self.dx, dy: -self.dz, dz: self.dy }
+    }
+
+    pub fn rotate_y( &self ) -> Dir3<C> {
+        Dir3{ dx: self.dz, dy: self.dy, dz: -self.dx }
+    }
+
+    pub fn rotate_z( &self ) -> Dir3<C> {
+        Dir3{ dx: -self.dy, dy: self.dx, dz: self.dz }
+    }
+
+    pub fn reverse( &self ) -> Dir3<C> {
+        Dir3{ dx: -self.dx, dy: -self.dy, dz: -self.dz }
+    }
+
+    pub fn times( &self, n: C ) -> Dir3<C> {
+        Dir3{ dx: self.dx * n, dy: self.dy * n, dz: self.dz * n }
+    }
+}
+
+impl<C: Coord> Add for Dir3<C> {
+    type Output = Dir3<C>;
+
+    fn add( self, other: Dir3<C> ) -> Dir3<C> {
+        Dir3::add(&self, &other)
+    }
+}
+
+impl<C: Coord> Sub for Dir3<C> {
+    type Output = Dir3<C>;
+
+    fn sub( self, other: Dir3<C> ) -> Dir3<C> {
+        Dir3::add(&self, &other.reverse())
+    }
+}
+
+impl<C: Coord> Neg for Dir3<C> {
+    type Output = Dir3<C>;
+
+    fn neg( self ) -> Dir3<C> {
+        self.reverse()
+    }
+}
+
+impl<C: Coord> Mul<C> for Dir3<C> {
+    type Output = Dir3<C>;
+
+    fn mul( self, n: C ) -> Dir3<C> {
+        self.times(n)
+    }
+}
+
+impl<C: Coord> AddAssign for Dir3<C> {
+    fn add_assign( &mut self, other: Dir3<C> ) {
+        *self = Dir3::add(self, &other);
+    }
+}
+
+// An axis-aligned box, given by its corners with the smallest and largest
+// coordinates (both included).
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Box3<C = i16> {
+    pub min: Pos3<C>,
+    pub max: Pos3<C>,
+}
+
+impl<C: Coord> Box3<C> {
+    // The box with opposite corners `a` and `b`.
+    pub fn new( a: Pos3<C>, b: Pos3<C> ) -> Box3<C> {
+        Box3{
+            min: Pos3{ x: a.x.min(b.x), y: a.y.min(b.y), z: a.z.min(b.z) },
+            max: Pos3{ x: a.x.max(b.x), y: a.y.max(b.y), z: a.z.max(b.z) },
+        }
+    }
+
+    // The number of positions along each axis.
+    pub fn size( &self ) -> (i64, i64, i64) {
+        (
+            self.max.x.as_i64() - self.min.x.as_i64() + 1,
+            self.max.y.as_i64() - self.min.y.as_i64() + 1,
+            self.max.z.as_i64() - self.min.z.as_i64() + 1,
+        )
+    }
+
+    pub fn volume( &self ) -> i64 {
+        let (x, y, z) = self.size();
+        x * y * z
+    }
+
+    pub fn contains( &self, p: &Pos3<C> ) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x
+            && p.y >= self.min.y && p.y <= self.max.y
+            && p.z >= self.min.z && p.z <= self.max.z
+    }
+
+    // The box covered by both boxes, or None if they do not overlap.
+    pub fn intersect( &self, other: &Box3<C> ) -> Option<Box3<C>> {
+        // Each axis is checked before computing the next one, as most boxes
+        // do not overlap.
+        let (min_x, max_x) = (self.min.x.max(other.min.x), self.max.x.min(other.max.x));
+        if min_x > max_x {
+            return None;
+        }
+        let (min_y, max_y) = (self.min.y.max(other.min.y), self.max.y.min(other.max.y));
+        if min_y > max_y {
+            return None;
+        }
+        let (min_z, max_z) = (self.min.z.max(other.min.z), self.max.z.min(other.max.z));
+        if min_z > max_z {
+            return None;
+        }
+        Some(Box3{ min: Pos3{ x: min_x, y: min_y, z: min_z }, max: Pos3{ x: max_x, y: max_y, z: max_z } })
+    }
+
+    // The smallest box containing both boxes.
+    pub fn union( &self, other: &Box3<C> ) -> Box3<C> {
+        Box3::new(
+            Pos3{ x: self.min.x.min(other.min.x), y: self.min.y.min(other.min.y), z: self.min.z.min(other.min.z) },
+            Pos3{ x: self.max.x.max(other.max.x), y: self.max.y.max(other.max.y), z: self.max.z.max(other.max.z) },
+        )
+    }
+
+    // The eight corners, with x changing fastest, then y, then z.
+    pub fn corners( &self ) -> impl Iterator<Item = Pos3<C>> {
+        let (min, max) = (self.min, self.max);
+        (0..8).map(move |i| Pos3{
+            x: if i & 1 == 0 { min.x } else { max.x },
+            y: if i & 2 == 0 { min.y } else { max.y },
+            z: if i & 4 == 0 { min.z } else { max.z },
+        })
+    }
+}
+
+// Parses the `x=a..b, y=c..d, z=e..f` notation.
+impl<C: Coord + FromStr> FromStr for Box3<C> {
+    type Err = ParseRangesError;
+
+    fn from_str( s: &str ) -> Result<Self, Self::Err> {
+        let [(x0, x1), (y0, y1), (z0, z1)] = parse_ranges(s, ["x", "y", "z"])?;
+        Ok(Box3::new(Pos3{ x: x0, y: y0, z: z0 }, Pos3{ x: x1, y: y1, z: z1 }))
+    }
+}
+
+// One of the 24 rotations that map the axes onto the axes, represented
+// by the directions the unit vectors along x, y and z are mapped to.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Rotation3 {
+    x: Dir3,
+    y: Dir3,
+    z: Dir3,
+}
+
+impl Rotation3 {
+    pub const IDENTITY: Rotation3 = Rotation3{ x: consts::PLUS_X, y: consts::PLUS_Y, z: consts::PLUS_Z };
+
+    // All the 24 rotations, starting from the identity.
+    pub fn all() -> [Rotation3; 24] {
+        let mut rotations = [Rotation3::IDENTITY; 24];
+        let mut n = 1;
+        let mut i = 0;
+
+        // Close the set under 90 degree rotations around x and y, which
+        // generate the whole group.
+        while n < 24 {
+            for r in [rotations[i].then_x(), rotations[i].then_y()] {
+                if !rotations[..n].contains(&r) {
+                    rotations[n] = r;
+                    n += 1;
+                }
+            }
+            i += 1;
+        }
+        rotations
+    }
+
+    fn then_x( &self ) -> Rotation3 {
+        Rotation3{ x: self.x.rotate_x(), y: self.y.rotate_x(), z: self.z.rotate_x() }
+    }
+
+    fn then_y( &self ) -> Rotation3 {
+        Rotation3{ x: self.x.rotate_y(), y: self.y.rotate_y(), z: self.z.rotate_y() }
+    }
+
+    pub fn apply( &self, d: &Dir3 ) -> Dir3 {
+        self.x * d.dx + self.y * d.dy + self.z * d.dz
+    }
+
+    // The rotation obtained applying first `self`, then `other`.
+    pub fn then( &self, other: &Rotation3 ) -> Rotation3 {
+        Rotation3{ x: other.apply(&self.x), y: other.apply(&self.y), z: other.apply(&self.z) }
+    }
+
+    pub fn inverse( &self ) -> Rotation3 {
+        // The inverse of a rotation matrix is its transpose.
+        Rotation3{
+            x: Dir3{ dx: self.x.dx, dy: self.y.dx, dz: self.z.dx },
+            y: Dir3{ dx: self.x.dy, dy: self.y.dy, dz: self.z.dy },
+            z: Dir3{ dx: self.x.dz, dy: self.y.dz, dz: self.z.dz },
+        }
+    }
+}
+
+// A fixed-size three-dimensional grid of values of type `T` (bytes by
+// default), covering the positions with coordinates from 0 to the size
+// along each axis. Values are accessed by Pos3 with get()/get_mut()/set().
+#[derive(Clone, Debug)]
+pub struct Grid3<T = u8> {
+    size_x: usize,
+    size_y: usize,
+    size_z: usize,
+    data: Vec<T>,
+}
+
+impl<T: Clone + Default> Grid3<T> {
+    pub fn new( size_x: usize, size_y: usize, size_z: usize ) -> Grid3<T> {
+        Grid3::filled(size_x, size_y, size_z, T::default())
+    }
+}
+
+impl<T: Clone> Grid3<T> {
+    pub fn filled( size_x: usize, size_y: usize, size_z: usize, v: T ) -> Grid3<T> {
+        assert!(size_x > 0 && size_x < i16::MAX as usize);
+        assert!(size_y > 0 && size_y < i16::MAX as usize);
+        assert!(size_z > 0 && size_z < i16::MAX as usize);
+        Grid3{ size_x, size_y, size_z, data: vec![v; size_x*size_y*size_z] }
+    }
+
+    pub fn size_x( &self ) -> usize {
+        self.size_x
+    }
+
+    pub fn size_y( &self ) -> usize {
+        self.size_y
+    }
+
+    pub fn size_z( &self ) -> usize {
+        self.size_z
+    }
+
+    fn index( &self, p: &Pos3 ) -> Option<usize> {
+        if p.x >= 0 && (p.x as usize) < self.size_x
+            && p.y >= 0 && (p.y as usize) < self.size_y
+            && p.z >= 0 && (p.z as usize) < self.size_z
+        {
+            Some((p.z as usize * self.size_y + p.y as usize) * self.size_x + p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    // The position of the value at index `i` in the data of a grid with
+    // the given sizes along x and y.
+    fn pos( size_x: usize, size_y: usize, i: usize ) -> Pos3 {
+        Pos3{
+            x: (i % size_x) as i16,
+            y: (i / size_x % size_y) as i16,
+            z: (i / size_x / size_y) as i16,
+        }
+    }
+
+    pub fn get( &self, p: &Pos3 ) -> Option<&T> {
+        self.index(p).map(|i| &self.data[i])
+    }
+
+    pub fn get_mut( &mut self, p: &Pos3 ) -> Option<&mut T> {
+        self.index(p).map(|i| &mut self.data[i])
+    }
+
+    pub fn set( &mut self, p: &Pos3, v: T ) {
+        let i = self.index(p).expect("position outside the grid");
+        self.data[i] = v;
+    }
+
+    pub fn entries( &self ) -> impl Iterator<Item = (Pos3, &T)> {
+        self.data.iter()
+            .enumerate()
+            .map(|(i, v)| (Self::pos(self.size_x, self.size_y, i), v))
+    }
+
+    pub fn entries_mut( &mut self ) -> impl Iterator<Item = (Pos3, &mut T)> {
+        let (size_x, size_y) = (self.size_x, self.size_y);
+        self.data.iter_mut()
+            .enumerate()
+            .map(move |(i, v)| (Self::pos(size_x, size_y, i), v))
+    }
+
+    // The positions next to `p` (along each axis) that are inside the
+    // grid, with their values.
+    pub fn neighbors6( &self, p: &Pos3 ) -> impl Iterator<Item = (Pos3, &T)> {
+        let p = *p;
+        consts::NEIGHBORS6.iter()
+            .map(move |d| p.go(*d))
+            .filter_map(|n| self.get(&n).map(|v| (n, v)))
+    }
+
+    pub fn values( &self ) -> impl Iterator<Item = &T> {
+        self.data.iter()
+    }
+}
+
+// A sparse three-dimensional grid of values of type `T`, with values
+// accessed by Pos3 with get()/insert()/remove().
+#[derive(Clone, Debug)]
+pub struct SparseGrid3<T> {
+    symbols: HashMap<Pos3, T>,
+}
+
+impl<T> SparseGrid3<T> {
+    pub fn new() -> Self {
+        SparseGrid3 { symbols: HashMap::new() }
+    }
+
+    // The smallest and largest coordinates along each axis, or None if
+    // the grid is empty.
+    pub fn bounds( &self ) -> Option<(Pos3, Pos3)> {
+        let mut positions = self.symbols.keys();
+        let first = *positions.next()?;
+        Some(positions.fold((first, first), |(min, max), p| (
+            Pos3{ x: min.x.min(p.x), y: min.y.min(p.y), z: min.z.min(p.z) },
+            Pos3{ x: max.x.max(p.x), y: max.y.max(p.y), z: max.z.max(p.z) },
+        )))
+    }
+
+    pub fn get( &self, p: &Pos3 ) -> Option<&T> {
+        self.symbols.get(p)
+    }
+
+    pub fn insert( &mut self, p: Pos3, v: T ) -> Option<T> {
+        self.symbols.insert(p, v)
+    }
+
+    pub fn remove( &mut self, p: &Pos3 ) -> Option<T> {
+        self.symbols.remove(p)
+    }
+
+    pub fn len( &self ) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn entries( &self ) -> impl Iterator<Item = (&Pos3, &T)> {
+        self.symbols.iter()
+    }
+
+    // The positions next to `p` (along each axis) that hold a value, with
+    // their values.
+    pub fn neighbors6( &self, p: &Pos3 ) -> impl Iterator<Item = (Pos3, &T)> {
+        let p = *p;
+        consts::NEIGHBORS6.iter()
+            .map(move |d| p.go(*d))
+            .filter_map(|n| self.get(&n).map(|v| (n, v)))
+    }
+}
+
+impl<T> Default for SparseGrid3<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::consts::*;
+
+    #[test]
+    fn rotations() {
+        let rotations = Rotation3::all();
+        for r in &rotations {
+            assert_eq!(r.then(&r.inverse()), Rotation3::IDENTITY);
+            for s in &rotations {
+                assert!(rotations.contains(&r.then(s)));
+            }
+        }
+        let d = Dir3{ dx: 1, dy: 2, dz: 3 };
+        let images: std::collections::HashSet<_> = rotations.iter().map(|r| r.apply(&d)).collect();
+        assert_eq!(images.len(), 24);
+        assert_eq!(PLUS_X.rotate_z(), PLUS_Y);
+        assert_eq!(PLUS_Y.rotate_x(), PLUS_Z);
+        assert_eq!(PLUS_Z.rotate_y(), PLUS_X);
+    }
+
+    #[test]
+    fn operators() {
+        let p: Pos3<i64> = Pos3{ x: 1, y: 2, z: 3 };
+        let d = Dir3{ dx: 1, dy: 0, dz: -1 };
+        assert_eq!(p + d * 2 - d, Pos3{ x: 2, y: 2, z: 2 });
+        assert_eq!((p + d) - p, d);
+        assert_eq!(p.distance(&Pos3::default()), 6);
+        assert_eq!(p.chebyshev_distance(&Pos3::default()), 3);
+        assert_eq!(p.squared_distance(&Pos3::default()), 14);
+    }
+
+    #[test]
+    fn boxes() {
+        let b: Box3<i64> = "x=10..12,y=10..12,z=10..12".parse().unwrap();
+        let c: Box3<i64> = "x=11..13, y=11..13, z=9..11".parse().unwrap();
+        assert_eq!(b.volume(), 27);
+        assert_eq!(b.intersect(&c), Some("x=11..12,y=11..12,z=10..11".parse().unwrap()));
+        assert_eq!(b.intersect(&Box3::default()), None);
+        assert_eq!(b.union(&c), Box3::new(Pos3{ x: 10, y: 10, z: 9 }, Pos3{ x: 13, y: 13, z: 12 }));
+        assert!(b.corners().all(|p| b.contains(&p)));
+        assert_eq!(b.corners().collect::<std::collections::HashSet<_>>().len(), 8);
+        assert!(!b.contains(&Pos3{ x: 10, y: 13, z: 10 }));
+        assert!("x=1..2,y=1..2".parse::<Box3>().is_err());
+    }
+
+    #[test]
+    fn grid() {
+        let mut g = Grid3::filled(2, 3, 4, 0);
+        assert_eq!((g.size_x(), g.size_y(), g.size_z()), (2, 3, 4));
+        let p = Pos3{ x: 1, y: 2, z: 3 };
+        g.set(&p, 7);
+        *g.get_mut(&ORIGIN).unwrap() = 1;
+        assert_eq!(g.get(&p), Some(&7));
+        assert_eq!(g.get(&p.go(PLUS_X)), None);
+        assert_eq!(g.get(&Pos3{ x: 0, y: 0, z: -1 }), None);
+
+        // Positions round-trip through the indexes of the data.
+        assert!(g.entries().all(|(q, v)| g.get(&q) == Some(v)));
+        assert_eq!(g.entries().filter(|(_, v)| **v != 0).map(|(q, _)| q).collect::<Vec<_>>(), [ORIGIN, p]);
+        for (q, v) in g.entries_mut() {
+            *v = q.x + 10 * q.y + 100 * q.z;
+        }
+        assert_eq!(g.get(&p), Some(&321));
+        assert_eq!(g.values().count(), 24);
+
+        // Corners have 3 neighbours, positions on a single face 5, and inner
+        // positions 6.
+        let mut n: Vec<_> = g.neighbors6(&ORIGIN).map(|(q, v)| (q, *v)).collect();
+        n.sort_by_key(|(_, v)| *v);
+        assert_eq!(n, [(Pos3{ x: 1, y: 0, z: 0 }, 1), (Pos3{ x: 0, y: 1, z: 0 }, 10), (Pos3{ x: 0, y: 0, z: 1 }, 100)]);
+        assert_eq!(g.neighbors6(&Pos3{ x: 1, y: 1, z: 1 }).count(), 5);
+        assert_eq!(Grid3::filled(3, 3, 3, 0).neighbors6(&Pos3{ x: 1, y: 1, z: 1 }).count(), 6);
+    }
+
+    #[test]
+    fn sparse_grid() {
+        let mut g = SparseGrid3::new();
+        assert!(g.is_empty() && g.bounds().is_none());
+        g.insert(ORIGIN, 'a');
+        g.insert(Pos3{ x: 2, y: -1, z: 0 }, 'b');
+        g.insert(Pos3{ x: 0, y: 0, z: 1 }, 'c');
+        assert_eq!(g.insert(ORIGIN, 'd'), Some('a'));
+        assert_eq!(g.len(), 3);
+        assert_eq!(g.bounds(), Some((Pos3{ x: 0, y: -1, z: 0 }, Pos3{ x: 2, y: 0, z: 1 })));
+        assert_eq!(g.neighbors6(&ORIGIN).collect::<Vec<_>>(), [(Pos3{ x: 0, y: 0, z: 1 }, &'c')]);
+        assert_eq!(g.remove(&ORIGIN), Some('d'));
+        assert_eq!(g.get(&ORIGIN), None);
+        assert_eq!(g.neighbors6(&Pos3{ x: 0, y: 0, z: 2 }).count(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// A colour, as red, green and blue components.
+pub type Rgb = [u8; 3];
+
+// Writes a `rows` x `cols` image in the binary PBM format, where `black`
+// tells if the pixel at the given row/column is black.
+pub fn write_pbm<W, F>( w: &mut W, rows: usize, cols: usize, black: F ) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(usize, usize) -> bool,
+{
+    writeln!(w, "P4\n{} {}", cols, rows)?;
+    let mut line = vec![0; cols.div_ceil(8)];
+    for r in 0..rows {
+        line.fill(0);
+        for c in 0..cols {
+            if black(r, c) {
+                line[c / 8] |= 0x80 >> (c % 8);
+            }
+        }
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// Writes a `rows` x `cols` image in the binary PGM format, where `gray`
+// gives the level of the pixel at the given row/column (0 is black).
+pub fn write_pgm<W, F>( w: &mut W, rows: usize, cols: usize, gray: F ) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(usize, usize) -> u8,
+{
+    writeln!(w, "P5\n{} {}\n255", cols, rows)?;
+    for r in 0..rows {
+        let line: Vec<_> = (0..cols).map(|c| gray(r, c)).collect();
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// Writes a `rows` x `cols` image in the binary PPM format, where `color`
+// gives the colour of the pixel at the given row/column.
+pub fn write_ppm<W, F>( w: &mut W, rows: usize, cols: usize, color: F ) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(usize, usize) -> Rgb,
+{
+    writeln!(w, "P6\n{} {}\n255", cols, rows)?;
+    for r in 0..rows {
+        let line: Vec<_> = (0..cols).flat_map(|c| color(r, c)).collect();
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// Writes a `rows` x `cols` image in the SVG format, with a square of side
+// `cell_size` for each pixel. The pixels for which `color` returns None
+// are left transparent.
+pub fn write_svg<W, F>( w: &mut W, rows: usize, cols: usize, cell_size: usize, color: F ) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(usize, usize) -> Option<Rgb>,
+{
+    writeln!(w, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+        cols * cell_size, rows * cell_size, cols, rows)?;
+    for r in 0..rows {
+        for c in 0..cols {
+            if let Some([red, green, blue]) = color(r, c) {
+                writeln!(w, r##"<rect x="{}" y="{}" width="1" height="1" fill="#{:02x}{:02x}{:02x}"/>"##, c, r, red, green, blue)?;
+            }
+        }
+    }
+    writeln!(w, "</svg>")
+}
+
+// Maps `v` to a colour going from blue (for `min`) through green to red
+// (for `max`), for heatmaps.
+pub fn heatmap( v: i64, min: i64, max: i64 ) -> Rgb {
+    let t = if max > min { (v.clamp(min, max) - min) as f64 / (max - min) as f64 } else { 0.0 };
+    let level = |t: f64| (t * 255.0).round() as u8;
+    if t < 0.5 {
+        [0, level(2.0 * t), level(1.0 - 2.0 * t)]
+    } else {
+        [level(2.0 * t - 1.0), level(2.0 - 2.0 * t), 0]
+    }
+}
+
+// Writes `c` to a terminal with the ANSI escape codes to draw it with the
+// foreground colour `fg` (if any), in reverse video if `highlight` is true.
+pub fn write_ansi<W: std::fmt::Write>( w: &mut W, c: char, fg: Option<Rgb>, highlight: bool ) -> std::fmt::Result {
+    if let Some([r, g, b]) = fg {
+        write!(w, "\x1b[38;2;{};{};{}m", r, g, b)?;
+    }
+    if highlight {
+        write!(w, "\x1b[7m")?;
+    }
+    write!(w, "{}", c)?;
+    if fg.is_some() || highlight {
+        write!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+// A sequence of images written to numbered files in a directory (for
+// example frames/step00000.ppm, frames/step00001.ppm, ...), to be turned
+// into an animation with external tools.
+pub struct FrameSequence {
+    dir: PathBuf,
+    prefix: String,
+    extension: String,
+    frames: usize,
+}
+
+impl FrameSequence {
+    // Creates the directory `dir` if needed.
+    pub fn new<P: AsRef<Path>>( dir: P, prefix: &str, extension: &str ) -> io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(FrameSequence {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            extension: extension.to_string(),
+            frames: 0,
+        })
+    }
+
+    // The number of frames written so far.
+    pub fn frames( &self ) -> usize {
+        self.frames
+    }
+
+    // Writes the next frame with `write`, and returns the path of the file.
+    pub fn write_frame<F>( &mut self, write: F ) -> io::Result<PathBuf>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+    {
+        let path = self.dir.join(format!("{}{:05}.{}", self.prefix, self.frames, self.extension));
+        let mut w = BufWriter::new(File::create(&path)?);
+        write(&mut w)?;
+        w.flush()?;
+        self.frames += 1;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let mut w = vec![];
+        write_pbm(&mut w, 2, 9, |r, c| r == c).unwrap();
+        assert_eq!(w, b"P4\n9 2\n\x80\x00\x40\x00");
+
+        let mut w = vec![];
+        write_pgm(&mut w, 1, 2, |_, c| c as u8 * 255).unwrap();
+        assert_eq!(w, b"P5\n2 1\n255\n\x00\xff");
+
+        let mut w = vec![];
+        write_ppm(&mut w, 1, 1, |_, _| [1, 2, 3]).unwrap();
+        assert_eq!(w, b"P6\n1 1\n255\n\x01\x02\x03");
+
+        let mut w = vec![];
+        write_svg(&mut w, 1, 2, 10, |_, c| if c == 1 { Some([255, 0, 16]) } else { None }).unwrap();
+        assert_eq!(String::from_utf8(w).unwrap(), "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"10\" viewBox=\"0 0 2 1\" shape-rendering=\"crispEdges\">
+<rect x=\"1\" y=\"0\" width=\"1\" height=\"1\" fill=\"#ff0010\"/>
+</svg>
+");
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(heatmap(0, 0, 10), [0, 0, 255]);
+        assert_eq!(heatmap(5, 0, 10), [0, 255, 0]);
+        assert_eq!(heatmap(12, 0, 10), [255, 0, 0]);
+
+        let mut s = String::new();
+        write_ansi(&mut s, 'a', Some([1, 2, 3]), true).unwrap();
+        write_ansi(&mut s, 'b', None, false).unwrap();
+        assert_eq!(s, "\x1b[38;2;1;2;3m\x1b[7ma\x1b[0mb");
+    }
+
+    #[test]
+    fn frames() {
+        let dir = std::env::temp_dir().join(format!("aoc-frames-{}", std::process::id()));
+        let mut frames = FrameSequence::new(&dir, "step", "pgm").unwrap();
+        for level in [0, 128] {
+            frames.write_frame(|w| write_pgm(w, 1, 1, |_, _| level)).unwrap();
+        }
+        assert_eq!(frames.frames(), 2);
+        assert_eq!(std::fs::read(dir.join("step00001.pgm")).unwrap(), b"P5\n1 1\n255\n\x80");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
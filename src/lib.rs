@@ -1,2 +1,6 @@
+pub mod automaton;
 pub mod graph;
 pub mod grid;
+pub mod grid3;
+pub mod image;
+pub mod snapshot;
@@ -0,0 +1,354 @@
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use crate::grid::{Coord, Dir, Pos, SimpleGrid, SparseGrid};
+
+// Snapshots start with these bytes, followed by the format version as a
+// little-endian u16. The version must be increased when the encoding of
+// any type changes, so that old snapshots are rejected rather than read
+// wrongly.
+pub const MAGIC: [u8; 4] = *b"AOCS";
+pub const VERSION: u16 = 1;
+
+// A value that can be saved in a compact binary form and read back.
+// Integers are stored in little-endian order, and collections as their
+// length (as a u64) followed by their elements.
+pub trait Snapshot: Sized {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()>;
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self>;
+}
+
+// Writes `v` with the snapshot header.
+pub fn write_snapshot<W: Write, S: Snapshot>( w: &mut W, v: &S ) -> io::Result<()> {
+    w.write_all(&MAGIC)?;
+    VERSION.write_to(w)?;
+    v.write_to(w)
+}
+
+// Reads a value written by write_snapshot(), failing with an error of
+// kind InvalidData if the header is not the one of the current version.
+pub fn read_snapshot<R: Read, S: Snapshot>( r: &mut R ) -> io::Result<S> {
+    read_header(r)?;
+    S::read_from(r)
+}
+
+// Same as read_snapshot() for a SparseGrid, which can also have been saved
+// with an index (the index is enabled again).
+pub fn read_sparse_snapshot<R, T>( r: &mut R ) -> io::Result<SparseGrid<T>>
+where
+    R: Read,
+    T: Clone + Eq + Hash + Snapshot,
+{
+    read_header(r)?;
+    let (mut grid, indexed) = read_sparse_grid(r)?;
+    if indexed {
+        grid.enable_index();
+    }
+    Ok(grid)
+}
+
+fn read_header<R: Read>( r: &mut R ) -> io::Result<()> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("not a snapshot"));
+    }
+    let version = u16::read_from(r)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported snapshot version {}", version)));
+    }
+    Ok(())
+}
+
+// Saves `v` to the file at `path`, replacing it if it exists.
+pub fn save<P: AsRef<Path>, S: Snapshot>( path: P, v: &S ) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write_snapshot(&mut w, v)?;
+    w.flush()
+}
+
+// Loads a value saved with save().
+pub fn load<P: AsRef<Path>, S: Snapshot>( path: P ) -> io::Result<S> {
+    read_snapshot(&mut BufReader::new(File::open(path)?))
+}
+
+fn invalid_data( message: &str ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+macro_rules! impl_snapshot_num {
+    ($($t:ty),*) => {
+        $(
+            impl Snapshot for $t {
+                fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+
+                fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    r.read_exact(&mut bytes)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_snapshot_num!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+// Stored as a u64, so that snapshots do not depend on the platform.
+impl Snapshot for usize {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        (*self as u64).write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        usize::try_from(u64::read_from(r)?).map_err(|_| invalid_data("length too large"))
+    }
+}
+
+impl Snapshot for isize {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        (*self as i64).write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        isize::try_from(i64::read_from(r)?).map_err(|_| invalid_data("integer too large"))
+    }
+}
+
+impl Snapshot for bool {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        (*self as u8).write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool")),
+        }
+    }
+}
+
+impl Snapshot for char {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        (*self as u32).write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        char::from_u32(u32::read_from(r)?).ok_or_else(|| invalid_data("invalid char"))
+    }
+}
+
+impl<T: Snapshot> Snapshot for Option<T> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        self.is_some().write_to(w)?;
+        match self {
+            Some(v) => v.write_to(w),
+            None => Ok(()),
+        }
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        Ok(if bool::read_from(r)? { Some(T::read_from(r)?) } else { None })
+    }
+}
+
+impl<T: Snapshot> Snapshot for Vec<T> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        self.len().write_to(w)?;
+        self.iter().try_for_each(|v| v.write_to(w))
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        let len = usize::read_from(r)?;
+        // Do not trust the length for the allocation, the data could be
+        // truncated or corrupted.
+        let mut v = Vec::with_capacity(len.min(4096));
+        for _ in 0..len {
+            v.push(T::read_from(r)?);
+        }
+        Ok(v)
+    }
+}
+
+impl<C: Coord + Snapshot> Snapshot for Pos<C> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        self.x.write_to(w)?;
+        self.y.write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        Ok(Pos{ x: C::read_from(r)?, y: C::read_from(r)? })
+    }
+}
+
+impl<C: Coord + Snapshot> Snapshot for Dir<C> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        self.dx.write_to(w)?;
+        self.dy.write_to(w)
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        Ok(Dir{ dx: C::read_from(r)?, dy: C::read_from(r)? })
+    }
+}
+
+// Stored as the number of rows and columns, the wrapping mode and the
+// values row by row.
+impl<T: Clone + Snapshot> Snapshot for SimpleGrid<T> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        self.rows().write_to(w)?;
+        self.cols().write_to(w)?;
+        self.is_wrapping().write_to(w)?;
+        self.values().try_for_each(|v| v.write_to(w))
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        let rows = usize::read_from(r)?;
+        let cols = usize::read_from(r)?;
+        if rows == 0 || cols == 0 || rows >= i16::MAX as usize || cols >= i16::MAX as usize {
+            return Err(invalid_data("invalid grid size"));
+        }
+        let wrapping = bool::read_from(r)?;
+        let len = rows.checked_mul(cols).ok_or_else(|| invalid_data("invalid grid size"))?;
+        // As for Vec, do not trust the size for the allocation.
+        let mut values = Vec::with_capacity(len.min(4096));
+        for _ in 0..len {
+            values.push(T::read_from(r)?);
+        }
+        let mut values = values.into_iter();
+        let mut grid = SimpleGrid::from_fn(rows, cols, |_, _| values.next().unwrap());
+        grid.set_wrapping(wrapping);
+        Ok(grid)
+    }
+}
+
+// Stored as whether the grid is indexed and the entries sorted by
+// position, so that equal grids give the same bytes. The index needs
+// values that can be hashed, so indexed grids can only be read with
+// read_sparse_snapshot().
+impl<T: Clone + Snapshot> Snapshot for SparseGrid<T> {
+    fn write_to<W: Write>( &self, w: &mut W ) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_by_key(|(p, _)| (p.y, p.x));
+        self.is_indexed().write_to(w)?;
+        entries.len().write_to(w)?;
+        entries.iter().try_for_each(|(p, v)| {
+            p.write_to(w)?;
+            v.write_to(w)
+        })
+    }
+
+    fn read_from<R: Read>( r: &mut R ) -> io::Result<Self> {
+        match read_sparse_grid(r)? {
+            (_, true) => Err(invalid_data("indexed grid, see read_sparse_snapshot()")),
+            (grid, false) => Ok(grid),
+        }
+    }
+}
+
+// Reads a SparseGrid without its index, and whether it had one.
+fn read_sparse_grid<R: Read, T: Clone + Snapshot>( r: &mut R ) -> io::Result<(SparseGrid<T>, bool)> {
+    let indexed = bool::read_from(r)?;
+    let mut grid = SparseGrid::new();
+    for _ in 0..usize::read_from(r)? {
+        let p = Pos::read_from(r)?;
+        if grid.insert(p, T::read_from(r)?).is_some() {
+            return Err(invalid_data("duplicate position"));
+        }
+    }
+    Ok((grid, indexed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<S: Snapshot>( v: &S ) -> S {
+        let mut bytes = vec![];
+        write_snapshot(&mut bytes, v).unwrap();
+        read_snapshot(&mut bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn grids() {
+        let mut g = SimpleGrid::create_from("#..\n.#.\n");
+        g.set_wrapping(true);
+        assert_eq!(round_trip(&g), g);
+
+        let g = SimpleGrid::from_fn(2, 3, |r, c| if r == c { None } else { Some(r * 3 + c) });
+        assert_eq!(round_trip(&g), g);
+
+        let mut s = SparseGrid::parse("a.\n.b\n").unwrap();
+        let copy = round_trip(&s);
+        assert!(!copy.is_indexed());
+        assert_eq!(copy.to_string(), s.to_string());
+        s.enable_index();
+        let mut bytes = vec![];
+        write_snapshot(&mut bytes, &s).unwrap();
+        let e = read_snapshot::<_, SparseGrid>(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let copy = read_sparse_snapshot::<_, char>(&mut bytes.as_slice()).unwrap();
+        assert!(copy.is_indexed());
+        assert_eq!(copy.to_string(), s.to_string());
+        assert_eq!(copy.find(&'b'), Some(&Pos{ x: 1, y: 1 }));
+
+        // Values that cannot be hashed, without an index.
+        let mut f = SparseGrid::new();
+        f.insert(Pos{ x: -1, y: 3 }, 0.25);
+        assert_eq!(round_trip(&f).get(&Pos{ x: -1, y: 3 }), Some(&0.25));
+
+        let p: Pos<i64> = Pos{ x: -3_000_000_000, y: 7 };
+        assert_eq!(round_trip(&p), p);
+        assert_eq!(round_trip(&vec![Dir{ dx: 1, dy: -1 }]), vec![Dir{ dx: 1, dy: -1 }]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut bytes = vec![];
+        write_snapshot(&mut bytes, &SimpleGrid::create_from("ab\n")).unwrap();
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let e = read_snapshot::<_, SimpleGrid>(&mut &truncated[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut old = bytes.clone();
+        old[4] = 0;
+        let e = read_snapshot::<_, SimpleGrid>(&mut old.as_slice()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let e = read_snapshot::<_, SimpleGrid>(&mut &b"PNG\0\x01\x00"[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // A large size without the values fails without allocating them.
+        let mut large = vec![];
+        write_snapshot(&mut large, &30000usize).unwrap();
+        30000usize.write_to(&mut large).unwrap();
+        false.write_to(&mut large).unwrap();
+        let e = read_snapshot::<_, SimpleGrid>(&mut large.as_slice()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut duplicate = vec![];
+        write_snapshot(&mut duplicate, &false).unwrap();
+        2usize.write_to(&mut duplicate).unwrap();
+        let p: Pos = Pos{ x: 1, y: 2 };
+        for _ in 0..2 {
+            p.write_to(&mut duplicate).unwrap();
+            'a'.write_to(&mut duplicate).unwrap();
+        }
+        let e = read_snapshot::<_, SparseGrid>(&mut duplicate.as_slice()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn files() {
+        let path = std::env::temp_dir().join(format!("aoc-snapshot-{}", std::process::id()));
+        let g = SimpleGrid::create_from("123\n456\n");
+        save(&path, &g).unwrap();
+        assert_eq!(load::<_, SimpleGrid>(&path).unwrap(), g);
+        std::fs::remove_file(&path).unwrap();
+    }
+}